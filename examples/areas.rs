@@ -28,7 +28,9 @@ struct MyApp {
 
 impl Default for MyApp {
     fn default() -> Self {
-        let mut map = Map::new(OpenStreetMapConfig::default());
+        let mut map = Map::new(OpenStreetMapConfig::default()).with_context_menu(|ui, pos| {
+            ui.label(format!("{:.5}, {:.5}", pos.lat, pos.lon));
+        });
         let mut area_layer = AreaLayer::default();
         let (center_lon, center_lat) = map.center.into();
 
@@ -37,7 +37,7 @@ impl Default for MyApp {
 
         let mut map = Map::new(OpenStreetMapConfig::default());
         map.center = GeoPos::from((9.0, 55.0));
-        map.zoom = 8;
+        map.zoom = 8.0;
 
         // Deserialize the GeoJSON into the AreaLayer.
         let mut area_layer = AreaLayer::default();
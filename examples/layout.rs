@@ -2,7 +2,12 @@
 #![allow(rustdoc::missing_crate_level_docs)] // it's an example
 
 use eframe::egui;
-use egui_map_view::{Map, config::OpenStreetMapConfig};
+use egui_map_view::{
+    Map,
+    config::OpenStreetMapConfig,
+    controls::{CompassButton, HomeButton, ZoomButtons},
+    minimap::Minimap,
+};
 
 fn main() -> eframe::Result {
     let options = eframe::NativeOptions {
@@ -24,7 +29,12 @@ struct MyApp {
 impl Default for MyApp {
     fn default() -> Self {
         Self {
-            map: Map::new(OpenStreetMapConfig::default()),
+            map: Map::new(OpenStreetMapConfig::default())
+                .with_fill_available()
+                .with_minimap(Minimap::default())
+                .with_control(egui::Align2::RIGHT_TOP, ZoomButtons)
+                .with_control(egui::Align2::RIGHT_TOP, CompassButton)
+                .with_control(egui::Align2::RIGHT_TOP, HomeButton::default()),
         }
     }
 }
@@ -50,8 +60,7 @@ impl eframe::App for MyApp {
         egui::CentralPanel::default()
             .frame(egui::Frame::NONE)
             .show_inside(ui, |ui| {
-                ui.add_sized(ui.available_size_before_wrap(), &mut self.map)
-                    .clicked();
+                ui.add(&mut self.map);
             });
     }
 }
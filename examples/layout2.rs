@@ -24,7 +24,7 @@ struct MyApp {
 impl Default for MyApp {
     fn default() -> Self {
         Self {
-            map: Map::new(OpenStreetMapConfig::default()),
+            map: Map::new(OpenStreetMapConfig::default()).with_fill_available(),
         }
     }
 }
@@ -35,7 +35,7 @@ impl eframe::App for MyApp {
             ui.heading("EXAMPLE");
             ui.label(self.map.center.lon.to_string());
             ui.label(self.map.center.lat.to_string());
-            ui.label(self.map.zoom.to_string());
+            ui.label(format!("{:.1}", self.map.zoom));
 
             ui.horizontal(|ui| {
                 // By using a right-to-left layout, we can have a widget on the right
@@ -43,7 +43,7 @@ impl eframe::App for MyApp {
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     ui.label("right to map");
                     // The map widget will take up all the remaining space.
-                    if ui.add_sized(ui.available_size(), &mut self.map).clicked()
+                    if ui.add(&mut self.map).clicked()
                         && let Some(pos) = self.map.mouse_pos
                     {
                         println!("{},{}", pos.lon, pos.lat);
@@ -2,6 +2,7 @@
 #![allow(rustdoc::missing_crate_level_docs)] // it's an example
 
 use eframe::egui;
+use egui::vec2;
 use egui_map_view::{Map, config::OpenStreetMapConfig};
 
 fn main() -> eframe::Result {
@@ -24,7 +25,8 @@ struct MyApp {
 impl Default for MyApp {
     fn default() -> Self {
         Self {
-            map: Map::new(OpenStreetMapConfig::default()),
+            // A fixed-size map next to other widgets.
+            map: Map::new(OpenStreetMapConfig::default()).with_desired_size(vec2(400.0, 300.0)),
         }
     }
 }
@@ -35,7 +37,7 @@ impl eframe::App for MyApp {
             ui.heading("EXAMPLE");
             ui.label(self.map.center.lon.to_string());
             ui.label(self.map.center.lat.to_string());
-            ui.label(self.map.zoom.to_string());
+            ui.label(format!("{:.1}", self.map.zoom));
 
             ui.horizontal(|ui| {
                 if ui.add(&mut self.map).clicked()
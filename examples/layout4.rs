@@ -24,7 +24,10 @@ struct MyApp {
 impl Default for MyApp {
     fn default() -> Self {
         Self {
-            map: Map::new(OpenStreetMapConfig::default()),
+            // A wide map that leaves room for the labels below it.
+            map: Map::new(OpenStreetMapConfig::default())
+                .with_aspect_ratio(2.0)
+                .with_min_size(egui::vec2(256.0, 128.0)),
         }
     }
 }
@@ -35,7 +38,7 @@ impl eframe::App for MyApp {
             ui.heading("EXAMPLE");
             ui.label(self.map.center.lon.to_string());
             ui.label(self.map.center.lat.to_string());
-            ui.label(self.map.zoom.to_string());
+            ui.label(format!("{:.1}", self.map.zoom));
 
             ui.add(&mut self.map);
 
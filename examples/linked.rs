@@ -0,0 +1,63 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
+#![allow(rustdoc::missing_crate_level_docs)] // it's an example
+
+use eframe::egui;
+use egui_map_view::{
+    Map,
+    config::OpenStreetMapConfig,
+    link::{LinkOptions, ViewLink},
+};
+
+fn main() -> eframe::Result {
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([1000.0, 600.0]),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "Linked maps",
+        options,
+        Box::new(|_cc| Ok(Box::<MyApp>::default())),
+    )
+}
+
+struct MyApp {
+    left: Map,
+    right: Map,
+}
+
+impl Default for MyApp {
+    fn default() -> Self {
+        let link = ViewLink::new();
+        let options = LinkOptions {
+            crosshair: true,
+            ..LinkOptions::default()
+        };
+        Self {
+            left: Map::new(OpenStreetMapConfig::default())
+                .with_fill_available()
+                .with_view_link(&link, options),
+            // The right map shows the same center two zoom levels further out.
+            right: Map::new(OpenStreetMapConfig::default())
+                .with_fill_available()
+                .with_view_link(
+                    &link,
+                    LinkOptions {
+                        zoom_offset: -2.0,
+                        ..options
+                    },
+                ),
+        }
+    }
+}
+
+impl eframe::App for MyApp {
+    fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show_inside(ui, |ui| {
+            ui.columns(2, |columns| {
+                columns[0].add(&mut self.left);
+                columns[1].add(&mut self.right);
+            });
+        });
+    }
+}
@@ -0,0 +1,898 @@
+//! Caching of map tiles, in memory and persistently on disk.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use egui_map_view::{
+//!     Map,
+//!     cache::{DiskCache, MemoryLimit},
+//!     config::OpenStreetMapConfig,
+//! };
+//!
+//! let cache = DiskCache::new("/tmp/egui-map-view-tiles").max_size(256 * 1024 * 1024);
+//! let map = Map::new(OpenStreetMapConfig::default())
+//!     .with_disk_cache(cache)
+//!     .with_memory_limit(MemoryLimit::Bytes(128 * 1024 * 1024));
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{debug, warn};
+use reqwest::header::{self, HeaderMap};
+
+use crate::{Tile, TileId};
+
+/// File extension of the cached tile bytes.
+const TILE_EXTENSION: &str = "tile";
+
+/// File extension of the cached tile metadata.
+const META_EXTENSION: &str = "meta";
+
+/// File extension of the marker that keeps a cached tile from being evicted.
+const PIN_EXTENSION: &str = "pin";
+
+/// How long tiles are considered fresh if the server does not say otherwise.
+/// The OpenStreetMap tile usage policy asks clients to cache tiles for at least 7 days.
+const DEFAULT_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The amount of tiles kept in memory when no limit is given. At 256x256 pixels this is 128 MiB
+/// of texture data.
+const DEFAULT_MEMORY_TILES: usize = 512;
+
+/// How many decoded tiles a map or a tile layer keeps in memory, set with
+/// [`Map::with_memory_limit`](crate::Map::with_memory_limit) or
+/// `TileLayer::with_memory_limit`.
+///
+/// Tiles that are visible on the map are never evicted, so the cache can temporarily grow beyond
+/// its limit when the map is very large.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryLimit {
+    /// Keep at most this many tiles in memory.
+    Tiles(usize),
+
+    /// Keep at most this many bytes of decoded tile images in memory.
+    Bytes(usize),
+
+    /// Never evict tiles.
+    Unlimited,
+}
+
+impl Default for MemoryLimit {
+    fn default() -> Self {
+        Self::Tiles(DEFAULT_MEMORY_TILES)
+    }
+}
+
+/// Usage statistics of the in-memory tile cache.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryCacheStats {
+    /// The number of tiles in memory, in any state.
+    pub tiles: usize,
+
+    /// The number of tiles that have been loaded into textures.
+    pub loaded: usize,
+
+    /// The number of tiles that are still being loaded.
+    pub loading: usize,
+
+    /// The size of the loaded tile textures in bytes.
+    pub bytes: usize,
+
+    /// The number of tiles that have been evicted since the cache was created.
+    pub evicted: u64,
+
+    /// The budget of the cache.
+    pub limit: MemoryLimit,
+}
+
+/// A tile in the memory cache together with its bookkeeping.
+struct MemoryEntry {
+    tile: Tile,
+    /// The frame in which the tile was last requested.
+    last_used: u64,
+}
+
+/// The in-memory tile cache of a map or a tile layer.
+///
+/// Every tile requested during a frame is marked as used. At the end of the frame,
+/// [`MemoryCache::evict`] drops the least recently used tiles that were not requested during the
+/// frame until the cache fits in its [`MemoryLimit`].
+#[derive(Default)]
+pub(crate) struct MemoryCache {
+    tiles: HashMap<TileId, MemoryEntry>,
+    limit: MemoryLimit,
+    frame: u64,
+    evicted: u64,
+}
+
+impl MemoryCache {
+    /// Changes the budget of the cache. Takes effect on the next eviction.
+    pub(crate) fn set_limit(&mut self, limit: MemoryLimit) {
+        self.limit = limit;
+    }
+
+    /// Returns a tile without marking it as used.
+    pub(crate) fn get(&self, tile_id: &TileId) -> Option<&Tile> {
+        self.tiles.get(tile_id).map(|entry| &entry.tile)
+    }
+
+    /// Returns a tile, inserting it with `insert` if it is not in the cache yet, and marks it as
+    /// used during the current frame.
+    pub(crate) fn get_or_insert_with(
+        &mut self,
+        tile_id: TileId,
+        insert: impl FnOnce() -> Tile,
+    ) -> &mut Tile {
+        let frame = self.frame;
+        let entry = self.tiles.entry(tile_id).or_insert_with(|| MemoryEntry {
+            tile: insert(),
+            last_used: frame,
+        });
+        entry.last_used = frame;
+        &mut entry.tile
+    }
+
+    /// Drops the least recently used tiles that were not used during the current frame until the
+    /// cache fits in its budget, and starts a new frame.
+    ///
+    /// Tiles that were not used during the current frame and are still waiting for a download
+    /// worker are always dropped, so that tiles that scrolled out of view are not downloaded.
+    pub(crate) fn evict(&mut self) {
+        let frame = self.frame;
+        self.frame += 1;
+
+        self.tiles.retain(|_, entry| {
+            entry.last_used == frame
+                || !matches!(&entry.tile, Tile::Loading { download, .. } if download.is_queued())
+        });
+
+        let (mut used, max) = match self.limit {
+            MemoryLimit::Unlimited => return,
+            MemoryLimit::Tiles(max) => (self.tiles.len(), max),
+            MemoryLimit::Bytes(max) => (self.bytes(), max),
+        };
+        if used <= max {
+            return;
+        }
+
+        let mut candidates: Vec<_> = self
+            .tiles
+            .iter()
+            .filter(|(_, entry)| entry.last_used < frame)
+            .map(|(tile_id, entry)| (*tile_id, entry.last_used))
+            .collect();
+        candidates.sort_unstable_by_key(|(_, last_used)| *last_used);
+
+        for (tile_id, _) in candidates {
+            if used <= max {
+                break;
+            }
+            if let Some(entry) = self.tiles.remove(&tile_id) {
+                used -= match self.limit {
+                    MemoryLimit::Bytes(_) => tile_bytes(&entry.tile),
+                    _ => 1,
+                };
+                self.evicted += 1;
+            }
+        }
+    }
+
+    /// Removes all tiles from the cache. Queued downloads of the removed tiles are cancelled.
+    pub(crate) fn clear(&mut self) {
+        self.tiles.clear();
+    }
+
+    /// Removes the tiles for which `predicate` returns `true`, so that they are loaded again the
+    /// next time they are needed. Queued downloads of the removed tiles are cancelled.
+    pub(crate) fn invalidate(&mut self, predicate: impl Fn(&TileId) -> bool) {
+        self.tiles.retain(|tile_id, _| !predicate(tile_id));
+    }
+
+    /// Returns the usage statistics of the cache.
+    pub(crate) fn stats(&self) -> MemoryCacheStats {
+        let mut stats = MemoryCacheStats {
+            tiles: self.tiles.len(),
+            loaded: 0,
+            loading: 0,
+            bytes: 0,
+            evicted: self.evicted,
+            limit: self.limit,
+        };
+        for entry in self.tiles.values() {
+            match &entry.tile {
+                Tile::Loaded(_) => stats.loaded += 1,
+                Tile::Loading { .. } => stats.loading += 1,
+                Tile::Failed { .. } | Tile::Unknown => {}
+            }
+            stats.bytes += tile_bytes(&entry.tile);
+        }
+        stats
+    }
+
+    /// Returns the size of the loaded tile textures in bytes.
+    fn bytes(&self) -> usize {
+        self.tiles
+            .values()
+            .map(|entry| tile_bytes(&entry.tile))
+            .sum()
+    }
+}
+
+/// Returns the size of the texture of a tile in bytes, or zero if the tile is not loaded.
+fn tile_bytes(tile: &Tile) -> usize {
+    match tile {
+        Tile::Loaded(texture) => texture.byte_size(),
+        _ => 0,
+    }
+}
+
+/// Freshness and validation information stored next to a cached tile.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheMeta {
+    /// The point in time after which the tile must be revalidated with the server.
+    pub expires: Option<SystemTime>,
+
+    /// The `ETag` header returned by the server.
+    pub etag: Option<String>,
+
+    /// The `Last-Modified` header returned by the server.
+    pub last_modified: Option<String>,
+}
+
+impl CacheMeta {
+    /// Builds the metadata from the headers of a tile response.
+    ///
+    /// Returns `None` if the server does not allow the tile to be stored
+    /// (`Cache-Control: no-store`).
+    #[must_use]
+    pub fn from_headers(
+        headers: &HeaderMap,
+        now: SystemTime,
+        default_ttl: Duration,
+    ) -> Option<Self> {
+        let header_str = |name| headers.get(name).and_then(|v| v.to_str().ok());
+
+        let mut max_age = None;
+        if let Some(cache_control) = header_str(header::CACHE_CONTROL) {
+            for directive in cache_control.split(',').map(str::trim) {
+                let directive = directive.to_ascii_lowercase();
+                if directive == "no-store" {
+                    return None;
+                } else if directive == "no-cache" {
+                    max_age = Some(0);
+                } else if let Some(value) = directive.strip_prefix("max-age=")
+                    && max_age.is_none()
+                {
+                    max_age = value.trim_matches('"').parse::<u64>().ok();
+                }
+            }
+        }
+
+        let expires = if let Some(max_age) = max_age {
+            now + Duration::from_secs(max_age)
+        } else if let Some(expires) = header_str(header::EXPIRES) {
+            // An invalid date, such as "0", means the tile has already expired.
+            httpdate::parse_http_date(expires).unwrap_or(now)
+        } else {
+            now + default_ttl
+        };
+
+        Some(Self {
+            expires: Some(expires),
+            etag: header_str(header::ETAG).map(str::to_string),
+            last_modified: header_str(header::LAST_MODIFIED).map(str::to_string),
+        })
+    }
+
+    /// Returns `true` if the tile can be used without revalidating it with the server.
+    #[must_use]
+    pub fn is_fresh(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| now < expires)
+    }
+
+    /// Serializes the metadata into the simple `key=value` format used on disk.
+    fn to_file_string(&self) -> String {
+        let mut out = String::new();
+        if let Some(expires) = self.expires {
+            let secs = expires
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            out.push_str(&format!("expires={secs}\n"));
+        }
+        if let Some(etag) = &self.etag {
+            out.push_str(&format!("etag={etag}\n"));
+        }
+        if let Some(last_modified) = &self.last_modified {
+            out.push_str(&format!("last_modified={last_modified}\n"));
+        }
+        out
+    }
+
+    /// Parses the metadata from the format written by `to_file_string`.
+    fn from_file_str(s: &str) -> Self {
+        let mut meta = Self::default();
+        for (key, value) in s.lines().filter_map(|line| line.split_once('=')) {
+            match key {
+                "expires" => {
+                    meta.expires = value
+                        .parse::<u64>()
+                        .ok()
+                        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+                }
+                "etag" => meta.etag = Some(value.to_string()),
+                "last_modified" => meta.last_modified = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        meta
+    }
+}
+
+/// A tile read from the disk cache.
+#[derive(Clone, Debug)]
+pub struct CachedTile {
+    /// The encoded image bytes as they were received from the server.
+    pub bytes: Vec<u8>,
+
+    /// Freshness and validation information of the tile.
+    pub meta: CacheMeta,
+}
+
+/// A persistent tile cache that stores tiles in a directory on disk.
+///
+/// Tiles are stored as `{root}/{provider}/{z}/{x}/{y}.tile` where `provider` is the
+/// [`MapConfig::cache_key`](crate::config::MapConfig::cache_key) of the tile provider. When the
+/// cache grows beyond its maximum size, the least recently used tiles are removed until it is a
+/// tenth below the maximum size, so that the cache is not scanned for every stored tile. Tiles that
+/// have been [pinned](DiskCache::pin), like the ones of an offline region, are never removed, but
+/// they still count towards the size.
+///
+/// The cache is cheap to clone, and clones share the same size bookkeeping, so the same cache can
+/// be given to several maps and tile layers.
+#[derive(Clone)]
+pub struct DiskCache {
+    root: PathBuf,
+    max_size: Option<u64>,
+    default_ttl: Duration,
+    usage: Arc<Mutex<Usage>>,
+}
+
+/// The size bookkeeping of a [`DiskCache`], shared between its clones.
+#[derive(Default)]
+struct Usage {
+    /// Total size of the cached tiles in bytes. `None` until the cache directory has been scanned.
+    bytes: Option<u64>,
+
+    /// The size above which tiles are evicted, if pinned tiles keep the cache above its maximum
+    /// size. `None` to evict above the maximum size.
+    evict_above: Option<u64>,
+}
+
+impl DiskCache {
+    /// Creates a new disk cache that stores tiles under the given directory.
+    ///
+    /// The directory is created when the first tile is stored.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            max_size: None,
+            default_ttl: DEFAULT_TTL,
+            usage: Arc::new(Mutex::new(Usage::default())),
+        }
+    }
+
+    /// Sets the maximum size of the cache in bytes. By default, the cache size is unlimited.
+    #[must_use]
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Sets how long tiles are considered fresh when the server sends no caching headers.
+    #[must_use]
+    pub fn default_ttl(mut self, default_ttl: Duration) -> Self {
+        self.default_ttl = default_ttl;
+        self
+    }
+
+    /// Returns the directory where the tiles are stored.
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns how long tiles are considered fresh when the server sends no caching headers.
+    #[must_use]
+    pub fn ttl(&self) -> Duration {
+        self.default_ttl
+    }
+
+    /// Reads a tile from the cache.
+    ///
+    /// Reading a tile marks it as recently used. Stale tiles are returned as well, use
+    /// [`CacheMeta::is_fresh`] to check whether the tile needs to be revalidated.
+    #[must_use]
+    pub fn get(&self, provider: &str, tile_id: &TileId) -> Option<CachedTile> {
+        let tile_path = self.tile_path(provider, tile_id, TILE_EXTENSION);
+        let bytes = fs::read(&tile_path).ok()?;
+        let meta = fs::read_to_string(self.tile_path(provider, tile_id, META_EXTENSION))
+            .map(|s| CacheMeta::from_file_str(&s))
+            .unwrap_or_default();
+
+        // The modification time of the tile file doubles as its last access time for LRU eviction.
+        if let Err(e) = fs::File::options()
+            .write(true)
+            .open(&tile_path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+        {
+            debug!(
+                "Unable to update access time of {}: {e}",
+                tile_path.display()
+            );
+        }
+
+        Some(CachedTile { bytes, meta })
+    }
+
+    /// Stores a tile in the cache, evicting the least recently used tiles if the cache grows beyond
+    /// its maximum size.
+    pub fn put(
+        &self,
+        provider: &str,
+        tile_id: &TileId,
+        bytes: &[u8],
+        meta: &CacheMeta,
+    ) -> io::Result<()> {
+        let tile_path = self.tile_path(provider, tile_id, TILE_EXTENSION);
+        if let Some(parent) = tile_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut usage = self.lock_usage();
+        let total = usage
+            .bytes
+            .get_or_insert_with(|| self.scan().iter().map(|e| e.len).sum());
+
+        let old_len = fs::metadata(&tile_path).map(|m| m.len()).unwrap_or(0);
+        write_atomic(&tile_path, bytes)?;
+        self.put_meta(provider, tile_id, meta)?;
+
+        *total = (*total + bytes.len() as u64).saturating_sub(old_len);
+        self.evict(&mut usage);
+        Ok(())
+    }
+
+    /// Replaces the metadata of a cached tile, for example after the server confirmed that the
+    /// tile has not changed.
+    pub fn put_meta(&self, provider: &str, tile_id: &TileId, meta: &CacheMeta) -> io::Result<()> {
+        write_atomic(
+            &self.tile_path(provider, tile_id, META_EXTENSION),
+            meta.to_file_string().as_bytes(),
+        )
+    }
+
+    /// Keeps a tile from being evicted when the cache grows beyond its maximum size. The tile
+    /// does not have to be in the cache yet.
+    pub fn pin(&self, provider: &str, tile_id: &TileId) -> io::Result<()> {
+        let pin_path = self.tile_path(provider, tile_id, PIN_EXTENSION);
+        if let Some(parent) = pin_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(pin_path, [])
+    }
+
+    /// Lets a pinned tile be evicted again.
+    pub fn unpin(&self, provider: &str, tile_id: &TileId) -> io::Result<()> {
+        match fs::remove_file(self.tile_path(provider, tile_id, PIN_EXTENSION)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns `true` if the tile is pinned.
+    #[must_use]
+    pub fn is_pinned(&self, provider: &str, tile_id: &TileId) -> bool {
+        self.tile_path(provider, tile_id, PIN_EXTENSION).exists()
+    }
+
+    /// Returns the total size of the cached tiles in bytes.
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        *self
+            .lock_usage()
+            .bytes
+            .get_or_insert_with(|| self.scan().iter().map(|e| e.len).sum())
+    }
+
+    /// Removes all tiles from the cache.
+    pub fn clear(&self) -> io::Result<()> {
+        let mut usage = self.lock_usage();
+        match fs::remove_dir_all(&self.root) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        *usage = Usage {
+            bytes: Some(0),
+            evict_above: None,
+        };
+        Ok(())
+    }
+
+    fn lock_usage(&self) -> std::sync::MutexGuard<'_, Usage> {
+        self.usage
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn tile_path(&self, provider: &str, tile_id: &TileId, extension: &str) -> PathBuf {
+        self.root
+            .join(provider)
+            .join(tile_id.z.to_string())
+            .join(tile_id.x.to_string())
+            .join(format!("{}.{extension}", tile_id.y))
+    }
+
+    /// Removes the least recently used tiles that are not pinned until the cache is a tenth below
+    /// its maximum size, once it has grown beyond it.
+    fn evict(&self, usage: &mut Usage) {
+        let (Some(max_size), Some(used)) = (self.max_size, usage.bytes) else {
+            return;
+        };
+        if used <= usage.evict_above.unwrap_or(max_size) {
+            return;
+        }
+
+        let mut entries = self.scan();
+        entries.sort_by_key(|e| e.accessed);
+        let mut used: u64 = entries.iter().map(|e| e.len).sum();
+        let target = max_size - max_size / 10;
+
+        for entry in entries {
+            if used <= target {
+                break;
+            }
+            if entry.path.with_extension(PIN_EXTENSION).exists() {
+                continue;
+            }
+            match fs::remove_file(&entry.path) {
+                Ok(()) => {
+                    let _ = fs::remove_file(entry.path.with_extension(META_EXTENSION));
+                    used = used.saturating_sub(entry.len);
+                }
+                Err(e) => warn!("Unable to evict cached tile {}: {e}", entry.path.display()),
+            }
+        }
+
+        // Pinned tiles may keep the cache above its maximum size. The cache is scanned again only
+        // once it has grown by another tenth of the maximum size.
+        usage.bytes = Some(used);
+        usage.evict_above = (used > max_size).then(|| used + max_size / 10);
+    }
+
+    /// Lists all cached tile files.
+    fn scan(&self) -> Vec<CacheEntry> {
+        let mut entries = Vec::new();
+        let mut dirs = vec![self.root.clone()];
+        while let Some(dir) = dirs.pop() {
+            let Ok(read_dir) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in read_dir.flatten() {
+                let path = entry.path();
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                if metadata.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|ext| ext == TILE_EXTENSION) {
+                    entries.push(CacheEntry {
+                        path,
+                        len: metadata.len(),
+                        accessed: metadata.modified().unwrap_or(UNIX_EPOCH),
+                    });
+                }
+            }
+        }
+        entries
+    }
+}
+
+/// A tile file found while scanning the cache directory.
+struct CacheEntry {
+    path: PathBuf,
+    len: u64,
+    accessed: SystemTime,
+}
+
+/// Writes a file through a temporary file so that readers never see a partially written tile.
+fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp_path = path.with_extension(format!(
+        "tmp{}-{:?}",
+        std::process::id(),
+        std::thread::current().id()
+    ));
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(&tmp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp_path);
+    })
+}
+
+/// Derives a directory name for a tile provider from one of its tile URLs.
+///
+/// The name consists of the host of the URL followed by a hash of the whole URL, so that providers
+/// on the same host, or the same provider with a different API key, do not share tiles.
+pub(crate) fn cache_key_for_url(url: &str) -> String {
+    let host = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| "tiles".to_string());
+    let host: String = host
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{host}-{:016x}", fnv1a(url.as_bytes()))
+}
+
+/// A 64-bit FNV-1a hash. Unlike `DefaultHasher`, its output is stable across Rust releases, which
+/// matters because it ends up in directory names on disk.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    /// Creates an empty, unique cache directory for a test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("egui-map-view-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn disk_cache_put_get_roundtrip() {
+        let dir = test_dir("roundtrip");
+        let cache = DiskCache::new(&dir);
+        let tile_id = TileId { z: 3, x: 4, y: 5 };
+        let meta = CacheMeta {
+            expires: Some(UNIX_EPOCH + Duration::from_secs(1_000)),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+        };
+
+        assert!(cache.get("osm", &tile_id).is_none());
+        cache.put("osm", &tile_id, b"tile bytes", &meta).unwrap();
+
+        let cached = cache.get("osm", &tile_id).unwrap();
+        assert_eq!(cached.bytes, b"tile bytes");
+        assert_eq!(cached.meta, meta);
+        assert!(dir.join("osm/3/4/5.tile").exists());
+        assert!(cache.get("other", &tile_id).is_none());
+        assert_eq!(cache.size(), 10);
+
+        cache.clear().unwrap();
+        assert!(cache.get("osm", &tile_id).is_none());
+        assert_eq!(cache.size(), 0);
+    }
+
+    #[test]
+    fn disk_cache_evicts_least_recently_used() {
+        let dir = test_dir("evict");
+        let cache = DiskCache::new(&dir).max_size(25);
+        let meta = CacheMeta::default();
+        let tile = |y| TileId { z: 1, x: 0, y };
+
+        cache.put("osm", &tile(0), &[0; 10], &meta).unwrap();
+        cache.put("osm", &tile(1), &[1; 10], &meta).unwrap();
+
+        // Make the first tile the most recently used one.
+        let past = SystemTime::now() - Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(dir.join("osm/1/0/1.tile"))
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+        assert!(cache.get("osm", &tile(0)).is_some());
+
+        cache.put("osm", &tile(2), &[2; 10], &meta).unwrap();
+
+        assert!(cache.get("osm", &tile(0)).is_some());
+        assert!(cache.get("osm", &tile(1)).is_none());
+        assert!(cache.get("osm", &tile(2)).is_some());
+        assert_eq!(cache.size(), 20);
+
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn disk_cache_keeps_pinned_tiles() {
+        let dir = test_dir("pin");
+        let cache = DiskCache::new(&dir).max_size(25);
+        let meta = CacheMeta::default();
+        let tile = |y| TileId { z: 1, x: 0, y };
+
+        cache.pin("osm", &tile(0)).unwrap();
+        assert!(cache.is_pinned("osm", &tile(0)));
+        cache.put("osm", &tile(0), &[0; 10], &meta).unwrap();
+        cache.put("osm", &tile(1), &[1; 10], &meta).unwrap();
+        let past = SystemTime::now() - Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(dir.join("osm/1/0/0.tile"))
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+
+        // The pinned tile is the least recently used one, so the next one goes instead.
+        cache.put("osm", &tile(2), &[2; 10], &meta).unwrap();
+        assert!(cache.get("osm", &tile(0)).is_some());
+        assert!(cache.get("osm", &tile(1)).is_none());
+
+        cache.unpin("osm", &tile(0)).unwrap();
+        assert!(!cache.is_pinned("osm", &tile(0)));
+        cache.unpin("osm", &tile(0)).unwrap();
+
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn disk_cache_evicts_below_max_size() {
+        let dir = test_dir("low-water");
+        let cache = DiskCache::new(&dir).max_size(100);
+        let meta = CacheMeta::default();
+        let tile = |y| TileId { z: 5, x: 0, y };
+
+        for y in 0..11 {
+            cache.put("osm", &tile(y), &[0; 10], &meta).unwrap();
+        }
+        // Eviction makes room for a few more tiles at once.
+        assert_eq!(cache.size(), 90);
+
+        // Pinned tiles that do not fit are kept, and the cache is not evicted again until it has
+        // grown by a tenth of its maximum size.
+        for y in 11..23 {
+            cache.pin("osm", &tile(y)).unwrap();
+            cache.put("osm", &tile(y), &[0; 10], &meta).unwrap();
+        }
+        assert_eq!(cache.size(), 120);
+        assert_eq!(cache.lock_usage().evict_above, Some(120));
+        for y in 11..23 {
+            assert!(cache.get("osm", &tile(y)).is_some());
+        }
+
+        cache.clear().unwrap();
+        assert_eq!(cache.lock_usage().evict_above, None);
+    }
+
+    #[test]
+    fn memory_cache_evicts_least_recently_used_tiles() {
+        let mut cache = MemoryCache::default();
+        cache.set_limit(MemoryLimit::Tiles(2));
+        let tile = |x| TileId { z: 1, x, y: 0 };
+
+        for x in 0..3 {
+            cache.get_or_insert_with(tile(x), || Tile::Unknown);
+            cache.evict();
+        }
+        // Tile 0 is the least recently used one.
+        assert!(cache.get(&tile(0)).is_none());
+        assert!(cache.get(&tile(1)).is_some());
+
+        // Tiles used during the current frame are kept even if they exceed the limit.
+        for x in 3..6 {
+            cache.get_or_insert_with(tile(x), || Tile::Unknown);
+        }
+        cache.evict();
+        let stats = cache.stats();
+        assert_eq!(stats.tiles, 3);
+        assert_eq!(stats.evicted, 3);
+        assert!((3..6).all(|x| cache.get(&tile(x)).is_some()));
+    }
+
+    #[test]
+    fn memory_cache_invalidates_tiles() {
+        let mut cache = MemoryCache::default();
+        let tile = |x| TileId { z: 1, x, y: 0 };
+        for x in 0..2 {
+            cache.get_or_insert_with(tile(x), || Tile::Unknown);
+        }
+
+        cache.invalidate(|tile_id| tile_id.x == 0);
+        assert!(cache.get(&tile(0)).is_none());
+        assert!(cache.get(&tile(1)).is_some());
+    }
+
+    #[test]
+    fn memory_cache_evicts_by_bytes() {
+        let ctx = egui::Context::default();
+        let mut cache = MemoryCache::default();
+        cache.set_limit(MemoryLimit::Bytes(2 * 16 * 16 * 4));
+        let tile = |x| TileId { z: 1, x, y: 0 };
+
+        for x in 0..3 {
+            cache.get_or_insert_with(tile(x), || {
+                Tile::Loaded(ctx.load_texture(
+                    format!("tile_{x}"),
+                    egui::ColorImage::filled([16, 16], egui::Color32::WHITE),
+                    Default::default(),
+                ))
+            });
+            cache.evict();
+        }
+
+        let stats = cache.stats();
+        assert_eq!(stats.loaded, 2);
+        assert_eq!(stats.bytes, 2 * 16 * 16 * 4);
+        assert!(cache.get(&tile(0)).is_none());
+    }
+
+    #[test]
+    fn cache_meta_from_headers() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let ttl = Duration::from_secs(100);
+
+        let mut headers = HeaderMap::new();
+        let meta = CacheMeta::from_headers(&headers, now, ttl).unwrap();
+        assert_eq!(meta.expires, Some(now + ttl));
+
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("public, max-age=60"),
+        );
+        headers.insert(header::ETAG, HeaderValue::from_static("\"v1\""));
+        let meta = CacheMeta::from_headers(&headers, now, ttl).unwrap();
+        assert_eq!(meta.expires, Some(now + Duration::from_secs(60)));
+        assert_eq!(meta.etag.as_deref(), Some("\"v1\""));
+        assert!(meta.is_fresh(now));
+        assert!(!meta.is_fresh(now + Duration::from_secs(60)));
+
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        let meta = CacheMeta::from_headers(&headers, now, ttl).unwrap();
+        assert!(!meta.is_fresh(now));
+
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        assert!(CacheMeta::from_headers(&headers, now, ttl).is_none());
+
+        headers.remove(header::CACHE_CONTROL);
+        headers.insert(
+            header::EXPIRES,
+            HeaderValue::from_static("Thu, 01 Jan 1970 00:00:10 GMT"),
+        );
+        let meta = CacheMeta::from_headers(&headers, now, ttl).unwrap();
+        assert_eq!(meta.expires, Some(UNIX_EPOCH + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn cache_key_is_stable_and_filesystem_safe() {
+        let key = cache_key_for_url("https://tile.openstreetmap.org/0/0/0.png");
+        assert_eq!(
+            key,
+            cache_key_for_url("https://tile.openstreetmap.org/0/0/0.png")
+        );
+        assert!(key.starts_with("tile.openstreetmap.org-"));
+        assert_ne!(
+            key,
+            cache_key_for_url("https://tile.openstreetmap.org/0/0/0.png?api-key=1")
+        );
+        assert!(cache_key_for_url("not a url").starts_with("tiles-"));
+    }
+}
@@ -0,0 +1,398 @@
+//! Animated movement of the map view.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//! use egui_map_view::{
+//!     Map,
+//!     camera::{Animation, Easing},
+//!     config::OpenStreetMapConfig,
+//!     projection::GeoBounds,
+//! };
+//!
+//! let mut map = Map::new(OpenStreetMapConfig::default());
+//!
+//! // Fly to Helsinki over two seconds.
+//! map.fly_to((24.94, 60.17).into(), 12.0, Animation::new(Duration::from_secs(2)));
+//!
+//! // Show the whole of Finland, with 20 points of padding around it.
+//! let finland = GeoBounds::new((20.5, 59.7).into(), (31.6, 70.1).into());
+//! map.fit_bounds(finland, 20.0, Animation::default().with_easing(Easing::EaseOut));
+//! ```
+
+use std::time::Duration;
+
+use egui::{Rect, Vec2};
+
+use crate::projection::{GeoBounds, GeoPos, MapProjection};
+use crate::{TILE_SIZE, lat_to_y, lon_to_x, wrap_lon, x_to_lon, y_to_lat};
+
+/// How the progress of an animation speeds up and slows down.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Easing {
+    /// Constant speed.
+    Linear,
+
+    /// Starts slowly and speeds up.
+    EaseIn,
+
+    /// Starts fast and slows down.
+    EaseOut,
+
+    /// Starts slowly, speeds up and slows down again at the end.
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    /// Maps the elapsed share of the animation time, from 0.0 to 1.0, to the completed share of
+    /// the movement.
+    #[must_use]
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t * t,
+            Self::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Self::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
+
+/// The duration and easing of a camera movement.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Animation {
+    /// How long the movement takes. A zero duration moves the view at once.
+    pub duration: Duration,
+
+    /// How the movement speeds up and slows down.
+    pub easing: Easing,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(500))
+    }
+}
+
+impl Animation {
+    /// An animation of the given duration that eases in and out.
+    #[must_use]
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            easing: Easing::default(),
+        }
+    }
+
+    /// Moves the view at once, without animating.
+    #[must_use]
+    pub fn instant() -> Self {
+        Self::new(Duration::ZERO)
+    }
+
+    /// Sets the easing of the animation.
+    #[must_use]
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+}
+
+/// Settings of the glide that continues panning after the map is released during a drag, as on
+/// touch screens.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KineticPanning {
+    /// Multiplies the speed of the pointer at release to get the starting speed of the glide.
+    /// Zero disables gliding.
+    pub sensitivity: f32,
+
+    /// The share of its speed the glide loses every second, from 0.0 (never slows down) to 1.0
+    /// (stops at once).
+    pub decay: f32,
+
+    /// The speed in points per second below which the glide stops.
+    pub min_speed: f32,
+}
+
+impl Default for KineticPanning {
+    fn default() -> Self {
+        Self {
+            sensitivity: 1.0,
+            decay: 0.95,
+            min_speed: 10.0,
+        }
+    }
+}
+
+impl KineticPanning {
+    /// Settings that turn gliding off.
+    #[must_use]
+    pub fn disabled() -> Self {
+        Self {
+            sensitivity: 0.0,
+            ..Self::default()
+        }
+    }
+
+    /// Advances a glide at `velocity` points per second by `dt` seconds. Returns how far the map
+    /// moves and the new velocity, which is zero once the glide has stopped.
+    pub(crate) fn step(&self, velocity: Vec2, dt: f32) -> (Vec2, Vec2) {
+        let offset = velocity * dt;
+        let velocity = velocity * (1.0 - self.decay.clamp(0.0, 1.0)).powf(dt);
+        if velocity.length() < self.min_speed {
+            (offset, Vec2::ZERO)
+        } else {
+            (offset, velocity)
+        }
+    }
+}
+
+/// The path the view takes between two positions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Path {
+    /// Center and zoom level change together.
+    Ease,
+
+    /// The map zooms out in the middle of the movement, so that the start and the end are in
+    /// view at the same time.
+    Fly,
+}
+
+/// Where a camera movement ends.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Target {
+    /// A center and a zoom level.
+    View { center: GeoPos, zoom: f64 },
+
+    /// The view that fits a bounding box, with padding in points on every side.
+    Bounds { bounds: GeoBounds, padding: f32 },
+}
+
+/// A camera movement that starts the next time the map is shown, once the size of the widget is
+/// known.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CameraMove {
+    pub(crate) target: Target,
+    pub(crate) path: Path,
+    pub(crate) animation: Animation,
+}
+
+/// A running camera movement. Positions are in tiles of zoom level 0, where the world is a
+/// single tile.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CameraAnimation {
+    from: (f64, f64, f64),
+    to: (f64, f64, f64),
+
+    /// How many zoom levels the map zooms out in the middle of the movement.
+    arc: f64,
+
+    animation: Animation,
+    elapsed: f64,
+}
+
+impl CameraAnimation {
+    /// Plans the movement from the current view to the target.
+    ///
+    /// The target zoom level is limited to `zoom_limits`.
+    pub(crate) fn new(
+        camera_move: CameraMove,
+        center: GeoPos,
+        zoom: f64,
+        widget_rect: Rect,
+        zoom_limits: (f64, f64),
+    ) -> Self {
+        let (target_center, target_zoom) = match camera_move.target {
+            Target::View { center, zoom } => (center, zoom),
+            Target::Bounds { bounds, padding } => {
+                let projection = MapProjection::fit_bounds(bounds, widget_rect, padding);
+                (
+                    GeoPos {
+                        lon: projection.center_lon,
+                        lat: projection.center_lat,
+                    },
+                    projection.zoom,
+                )
+            }
+        };
+        let target_zoom = target_zoom.clamp(zoom_limits.0, zoom_limits.1);
+
+        let from = (lon_to_x(center.lon, 0.0), lat_to_y(center.lat, 0.0), zoom);
+        let to_x = lon_to_x(target_center.lon, 0.0);
+        let to = (
+            // Take the shorter way around the world, which may cross the antimeridian.
+            to_x - (to_x - from.0).round(),
+            lat_to_y(target_center.lat, 0.0),
+            target_zoom,
+        );
+
+        let arc = match camera_move.path {
+            Path::Ease => 0.0,
+            Path::Fly => {
+                // Zoom out until the distance between the start and the end fits the widget.
+                let distance = (to.0 - from.0).hypot(to.1 - from.1) * f64::from(TILE_SIZE);
+                let widget_size = f64::from(widget_rect.width().min(widget_rect.height()));
+                if distance > 0.0 && widget_size > 0.0 {
+                    let overview_zoom = (widget_size / distance).log2();
+                    (from.2.min(to.2) - overview_zoom.max(zoom_limits.0)).max(0.0)
+                } else {
+                    0.0
+                }
+            }
+        };
+
+        Self {
+            from,
+            to,
+            arc,
+            animation: camera_move.animation,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Advances the movement by `dt` seconds. Returns the new center and zoom level, and
+    /// whether the movement has finished.
+    pub(crate) fn step(&mut self, dt: f64) -> (GeoPos, f64, bool) {
+        self.elapsed += dt;
+        let duration = self.animation.duration.as_secs_f64();
+        let t = if duration > 0.0 {
+            (self.elapsed / duration).min(1.0)
+        } else {
+            1.0
+        };
+        let (center, zoom) = self.view_at(self.animation.easing.apply(t));
+        (center, zoom, t >= 1.0)
+    }
+
+    /// Returns the view when the share `t` of the movement is completed.
+    fn view_at(&self, t: f64) -> (GeoPos, f64) {
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        let center = GeoPos {
+            lon: wrap_lon(x_to_lon(lerp(self.from.0, self.to.0), 0.0)),
+            lat: y_to_lat(lerp(self.from.1, self.to.1), 0.0),
+        };
+        let zoom = lerp(self.from.2, self.to.2) - self.arc * 4.0 * t * (1.0 - t);
+        (center, zoom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::{pos2, vec2};
+
+    const EPSILON: f64 = 1e-9;
+
+    #[test]
+    fn easing_starts_and_ends_in_place() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert!(easing.apply(0.0).abs() < EPSILON);
+            assert!((easing.apply(1.0) - 1.0).abs() < EPSILON);
+        }
+        assert!((Easing::EaseInOut.apply(0.5) - 0.5).abs() < EPSILON);
+        assert!(Easing::EaseIn.apply(0.5) < 0.5);
+        assert!(Easing::EaseOut.apply(0.5) > 0.5);
+    }
+
+    #[test]
+    fn kinetic_panning_slows_down() {
+        let kinetic = KineticPanning {
+            sensitivity: 1.0,
+            decay: 0.75,
+            min_speed: 10.0,
+        };
+        let (offset, velocity) = kinetic.step(vec2(400.0, 0.0), 0.5);
+        assert_eq!(offset, vec2(200.0, 0.0));
+        assert!((velocity.x - 200.0).abs() < 1e-3);
+
+        // The glide stops once it is slow enough.
+        let (_, velocity) = kinetic.step(vec2(12.0, 0.0), 0.5);
+        assert_eq!(velocity, Vec2::ZERO);
+    }
+
+    #[test]
+    fn fly_zooms_out_in_the_middle() {
+        let rect = Rect::from_min_size(pos2(0.0, 0.0), vec2(800.0, 600.0));
+        let helsinki = GeoPos::from((24.94, 60.17));
+        let new_york = GeoPos::from((-74.0, 40.7));
+        let camera_move = CameraMove {
+            target: Target::View {
+                center: new_york,
+                zoom: 12.0,
+            },
+            path: Path::Fly,
+            animation: Animation::new(Duration::from_secs(2)).with_easing(Easing::Linear),
+        };
+        let mut animation = CameraAnimation::new(camera_move, helsinki, 12.0, rect, (0.0, 19.0));
+
+        let (_, zoom, finished) = animation.step(1.0);
+        assert!(zoom < 4.0);
+        assert!(!finished);
+
+        let (center, zoom, finished) = animation.step(1.0);
+        assert!((center.lon - new_york.lon).abs() < 1e-6);
+        assert!((center.lat - new_york.lat).abs() < 1e-6);
+        assert!((zoom - 12.0).abs() < EPSILON);
+        assert!(finished);
+    }
+
+    #[test]
+    fn ease_crosses_antimeridian() {
+        let rect = Rect::from_min_size(pos2(0.0, 0.0), vec2(800.0, 600.0));
+        let camera_move = CameraMove {
+            target: Target::View {
+                center: GeoPos::from((-170.0, 0.0)),
+                zoom: 5.0,
+            },
+            path: Path::Ease,
+            animation: Animation::new(Duration::from_secs(1)).with_easing(Easing::Linear),
+        };
+        let mut animation = CameraAnimation::new(
+            camera_move,
+            GeoPos::from((170.0, 0.0)),
+            5.0,
+            rect,
+            (0.0, 19.0),
+        );
+
+        // Halfway there is the antimeridian, not the prime meridian.
+        let (center, _, _) = animation.step(0.5);
+        assert!(center.lon.abs() > 179.0);
+    }
+
+    #[test]
+    fn target_zoom_is_limited() {
+        let rect = Rect::from_min_size(pos2(0.0, 0.0), vec2(800.0, 600.0));
+        let camera_move = CameraMove {
+            target: Target::View {
+                center: GeoPos::from((0.0, 0.0)),
+                zoom: 25.0,
+            },
+            path: Path::Ease,
+            animation: Animation::instant(),
+        };
+        let mut animation = CameraAnimation::new(
+            camera_move,
+            GeoPos::from((0.0, 0.0)),
+            5.0,
+            rect,
+            (0.0, 19.0),
+        );
+        let (_, zoom, finished) = animation.step(0.0);
+        assert!((zoom - 19.0).abs() < EPSILON);
+        assert!(finished);
+    }
+}
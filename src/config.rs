@@ -1,12 +1,33 @@
 //! Configuration for different map providers.
 
+use std::sync::Arc;
+
 use crate::TileId;
+use crate::http::HttpClient;
+use crate::source::TileSource;
 
 /// Configuration for a map provider.
 pub trait MapConfig {
     /// Returns the URL for a given tile.
     fn tile_url(&self, tile: &TileId) -> String;
 
+    /// Returns a source that provides the tile images directly, for example from a local file.
+    ///
+    /// If returns `None`, which is the default, the tiles are downloaded from
+    /// [`MapConfig::tile_url`].
+    fn tile_source(&self) -> Option<Arc<dyn TileSource>> {
+        None
+    }
+
+    /// Returns the HTTP client that downloads the tiles of this provider, for providers that
+    /// need request headers such as an access token, timeouts or a proxy.
+    ///
+    /// The client is requested for every tile, so build it once and return a clone. If returns
+    /// `None`, which is the default, a shared client with default settings is used.
+    fn http_client(&self) -> Option<HttpClient> {
+        None
+    }
+
     /// Returns the attribution text to be displayed on the map. If returns `None`, no attribution is shown.
     fn attribution(&self) -> Option<&String>;
 
@@ -28,6 +49,14 @@ pub trait MapConfig {
     fn max_zoom(&self) -> u8 {
         19
     }
+
+    /// Returns a key that keeps the tiles of this provider apart from other providers in a
+    /// [`DiskCache`](crate::cache::DiskCache). The key is used as a directory name.
+    ///
+    /// Defaults to the host of the tile URL followed by a hash of the URL of the root tile.
+    fn cache_key(&self) -> String {
+        crate::cache::cache_key_for_url(&self.tile_url(&TileId { z: 0, x: 0, y: 0 }))
+    }
 }
 
 /// Configuration for the OpenStreetMap tile server.
@@ -0,0 +1,243 @@
+//! Controls that are shown on top of the map.
+//!
+//! A control is anything that implements [`MapControl`]. Controls are added to a corner of the
+//! map with [`Map::with_control`], and the controls of the same corner are stacked from the
+//! corner inwards. The map comes with [`ZoomButtons`], a [`CompassButton`] that turns the map
+//! back to north-up, a [`HomeButton`] and a [`LayerSwitcher`].
+//!
+//! # Example
+//!
+//! ```
+//! use egui::Align2;
+//! use egui_map_view::{
+//!     Map,
+//!     config::OpenStreetMapConfig,
+//!     controls::{CompassButton, ControlContext, LayerSwitcher, MapControl, ZoomButtons},
+//! };
+//!
+//! /// Shows the zoom level of the map.
+//! struct ZoomLabel;
+//!
+//! impl MapControl for ZoomLabel {
+//!     fn ui(&mut self, ui: &mut egui::Ui, context: &mut ControlContext<'_>) {
+//!         ui.label(format!("{:.1}", context.map().zoom));
+//!     }
+//! }
+//!
+//! let map = Map::new(OpenStreetMapConfig::default())
+//!     .with_control(Align2::RIGHT_TOP, ZoomButtons)
+//!     .with_control(Align2::RIGHT_TOP, CompassButton)
+//!     .with_control(Align2::LEFT_TOP, LayerSwitcher::default())
+//!     .with_control(Align2::RIGHT_BOTTOM, ZoomLabel);
+//! ```
+
+use egui::emath::Rot2;
+use egui::{Button, Color32, Rect, Sense, Shape, Ui, Vec2, vec2};
+
+use crate::Map;
+use crate::camera::Animation;
+use crate::projection::GeoPos;
+
+/// The size of the buttons of the built-in controls.
+const BUTTON_SIZE: f32 = 24.0;
+
+/// A control that is shown on top of the map.
+pub trait MapControl {
+    /// Shows the control. `ui` is placed in the corner of the map the control was added to.
+    fn ui(&mut self, ui: &mut Ui, context: &mut ControlContext<'_>);
+}
+
+/// The map a control is on.
+pub struct ControlContext<'a> {
+    map: &'a mut Map,
+    rect: Rect,
+}
+
+impl<'a> ControlContext<'a> {
+    pub(crate) fn new(map: &'a mut Map, rect: Rect) -> Self {
+        Self { map, rect }
+    }
+
+    /// Returns the map.
+    pub fn map(&mut self) -> &mut Map {
+        self.map
+    }
+
+    /// Returns where the map is on the screen.
+    #[must_use]
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    /// Zooms the map in by `levels` whole levels around its center, or out if `levels` is
+    /// negative, like the zoom keys do.
+    pub fn zoom_by(&mut self, levels: i32) {
+        let target = self.map.zoom_target().round() + f64::from(levels);
+        self.map
+            .zoom_towards(target, self.rect.center(), &self.rect);
+    }
+
+    /// Turns the map back to north-up.
+    pub fn reset_bearing(&mut self) {
+        self.map.bearing = 0.0;
+    }
+}
+
+/// Buttons that zoom the map in and out by one level.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ZoomButtons;
+
+impl MapControl for ZoomButtons {
+    fn ui(&mut self, ui: &mut Ui, context: &mut ControlContext<'_>) {
+        ui.spacing_mut().item_spacing = Vec2::splat(2.0);
+        if ui
+            .add(Button::new("+").min_size(Vec2::splat(BUTTON_SIZE)))
+            .on_hover_text("Zoom in")
+            .clicked()
+        {
+            context.zoom_by(1);
+        }
+        if ui
+            .add(Button::new("−").min_size(Vec2::splat(BUTTON_SIZE)))
+            .on_hover_text("Zoom out")
+            .clicked()
+        {
+            context.zoom_by(-1);
+        }
+    }
+}
+
+/// A compass that shows where north is, and turns the map back to north-up when clicked.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CompassButton;
+
+impl MapControl for CompassButton {
+    fn ui(&mut self, ui: &mut Ui, context: &mut ControlContext<'_>) {
+        let (rect, response) = ui.allocate_exact_size(Vec2::splat(BUTTON_SIZE), Sense::click());
+        let response = response.on_hover_text("Reset north");
+        if ui.is_rect_visible(rect) {
+            let visuals = ui.style().interact(&response);
+            let painter = ui.painter();
+            painter.rect(
+                rect,
+                visuals.corner_radius,
+                visuals.weak_bg_fill,
+                visuals.bg_stroke,
+                egui::StrokeKind::Inside,
+            );
+
+            // The needle points to north on the map, which is turned by the bearing.
+            let rotation = Rot2::from_angle(-context.map.bearing.to_radians() as f32);
+            let center = rect.center();
+            let half_length = rect.height() * 0.35;
+            let half_width = rect.width() * 0.15;
+            let point = |offset: Vec2| center + rotation * offset;
+            let north = point(vec2(0.0, -half_length));
+            let south = point(vec2(0.0, half_length));
+            let left = point(vec2(-half_width, 0.0));
+            let right = point(vec2(half_width, 0.0));
+            painter.add(Shape::convex_polygon(
+                vec![north, right, left],
+                Color32::from_rgb(220, 50, 50),
+                egui::Stroke::NONE,
+            ));
+            painter.add(Shape::convex_polygon(
+                vec![south, left, right],
+                visuals.fg_stroke.color,
+                egui::Stroke::NONE,
+            ));
+        }
+        if response.clicked() {
+            context.reset_bearing();
+        }
+    }
+}
+
+/// A button that moves the map to its home view.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HomeButton {
+    /// The center and zoom level of the home view. The default view of the map configuration is
+    /// used if this is `None`.
+    pub home: Option<(GeoPos, f64)>,
+}
+
+impl MapControl for HomeButton {
+    fn ui(&mut self, ui: &mut Ui, context: &mut ControlContext<'_>) {
+        if ui
+            .add(Button::new("⌂").min_size(Vec2::splat(BUTTON_SIZE)))
+            .on_hover_text("Home")
+            .clicked()
+        {
+            let map = context.map();
+            let (center, zoom) = self.home.unwrap_or_else(|| {
+                (
+                    map.config.default_center().into(),
+                    f64::from(map.config.default_zoom()),
+                )
+            });
+            map.ease_to(center, zoom, Animation::default());
+        }
+    }
+}
+
+/// A menu for showing and hiding the layers of the map.
+#[derive(Clone, Debug)]
+pub struct LayerSwitcher {
+    /// The text of the button that opens the menu.
+    pub title: String,
+}
+
+impl Default for LayerSwitcher {
+    fn default() -> Self {
+        Self {
+            title: "Layers".to_owned(),
+        }
+    }
+}
+
+impl MapControl for LayerSwitcher {
+    fn ui(&mut self, ui: &mut Ui, context: &mut ControlContext<'_>) {
+        let map = context.map();
+        if map.layers().is_empty() {
+            return;
+        }
+        ui.menu_button(self.title.as_str(), |ui| {
+            let keys: Vec<String> = map.layers().keys().cloned().collect();
+            for key in keys {
+                let mut visible = map.is_layer_visible(&key);
+                if ui.checkbox(&mut visible, key.as_str()).changed() {
+                    map.set_layer_visible(&key, visible);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OpenStreetMapConfig;
+    use egui::pos2;
+
+    #[test]
+    fn control_context_moves_map() {
+        let mut map = Map::new(OpenStreetMapConfig::default());
+        map.zoom = 10.4;
+        map.bearing = 45.0;
+        let rect = Rect::from_min_size(pos2(0.0, 0.0), vec2(800.0, 600.0));
+        let mut context = ControlContext::new(&mut map, rect);
+
+        // Zooming snaps to whole levels, like the zoom keys.
+        context.zoom_by(1);
+        assert_eq!(context.map().zoom_target(), 11.0);
+        context.zoom_by(-2);
+        assert_eq!(context.map().zoom_target(), 9.0);
+
+        // The zoom levels of the configuration are kept.
+        context.zoom_by(100);
+        assert_eq!(context.map().zoom_target(), f64::from(crate::MAX_ZOOM));
+
+        context.reset_bearing();
+        assert_eq!(map.bearing, 0.0);
+    }
+}
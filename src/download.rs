@@ -0,0 +1,508 @@
+//! A bounded pool of worker threads that download map tiles.
+//!
+//! All maps and tile layers share [`DownloadPool::global`] unless they are given a pool of their
+//! own. Queued tiles are downloaded closest to the center of the map first, and tiles that
+//! scroll out of view before their download has started are dropped from the queue. Tiles that
+//! fail because of a transient error are retried according to a [`RetryPolicy`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use egui_map_view::{Map, config::OpenStreetMapConfig, download::DownloadPool};
+//!
+//! let pool = DownloadPool::with_limits(8, 4);
+//! let map = Map::new(OpenStreetMapConfig::default()).with_download_pool(pool);
+//! ```
+
+use std::collections::HashMap;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, LazyLock, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use log::warn;
+use poll_promise::{Promise, Sender};
+
+/// The number of worker threads of the global pool.
+const DEFAULT_WORKERS: usize = 6;
+
+/// The number of concurrent downloads from a single host. The OpenStreetMap tile usage policy
+/// asks clients to keep the number of parallel connections low.
+const DEFAULT_MAX_PER_HOST: usize = 2;
+
+static GLOBAL_POOL: LazyLock<DownloadPool> = LazyLock::new(DownloadPool::default);
+
+/// The result of loading a tile.
+pub(crate) type TileResult = Result<egui::ColorImage, Arc<eyre::Report>>;
+
+/// The work of loading a single tile.
+type Task = Box<dyn FnOnce() -> TileResult + Send>;
+
+/// A shared, size-limited queue of tile downloads served by a fixed number of worker threads.
+///
+/// The pool is cheap to clone, and clones share the same queue and workers. The worker threads
+/// stop when the last clone is dropped.
+#[derive(Clone)]
+pub struct DownloadPool {
+    handle: Arc<PoolHandle>,
+}
+
+impl Default for DownloadPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_WORKERS)
+    }
+}
+
+impl DownloadPool {
+    /// Creates a new pool that downloads at most `workers` tiles at the same time, and at most two
+    /// from a single host.
+    pub fn new(workers: usize) -> Self {
+        Self::with_limits(workers, DEFAULT_MAX_PER_HOST)
+    }
+
+    /// Creates a new pool that downloads at most `workers` tiles at the same time, and at most
+    /// `max_per_host` from a single host. Tiles read from local files are not limited per host.
+    pub fn with_limits(workers: usize, max_per_host: usize) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                queue: Vec::new(),
+                active_per_host: HashMap::new(),
+                max_per_host: max_per_host.max(1),
+                shutdown: false,
+            }),
+            condvar: Condvar::new(),
+        });
+
+        for i in 0..workers.max(1) {
+            let shared = shared.clone();
+            if let Err(e) = std::thread::Builder::new()
+                .name(format!("download_tile_{i}"))
+                .spawn(move || shared.work())
+            {
+                warn!("Unable to start tile download worker: {e}");
+            }
+        }
+
+        Self {
+            handle: Arc::new(PoolHandle { shared }),
+        }
+    }
+
+    /// Returns the pool that is used by maps and tile layers that have not been given a pool of
+    /// their own.
+    #[must_use]
+    pub fn global() -> Self {
+        GLOBAL_POOL.clone()
+    }
+
+    /// Returns the number of downloads that are waiting for a worker.
+    #[must_use]
+    pub fn queued(&self) -> usize {
+        self.handle.shared.lock().queue.len()
+    }
+
+    /// Queues a task that loads a tile from `host`, or from a local file if `host` is `None`.
+    /// Tasks with a lower `priority` run first.
+    pub(crate) fn spawn(
+        &self,
+        host: Option<String>,
+        priority: f32,
+        task: impl FnOnce() -> TileResult + Send + 'static,
+    ) -> Download {
+        let (sender, promise) = Promise::new();
+        let ticket = Arc::new(Ticket {
+            priority: AtomicU32::new(priority.to_bits()),
+            queued: AtomicBool::new(true),
+        });
+
+        let shared = &self.handle.shared;
+        shared.lock().queue.push(Job {
+            host,
+            ticket: ticket.clone(),
+            task: Box::new(task),
+            sender,
+        });
+        shared.condvar.notify_one();
+
+        Download {
+            promise,
+            ticket,
+            shared: shared.clone(),
+        }
+    }
+}
+
+/// How tiles that failed to load because of a transient error, such as a timeout or a server
+/// error, are retried. Tiles that do not exist on the server are never retried.
+///
+/// The delay before a retry doubles after every failed attempt, up to `max_delay`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times a tile is retried before giving up. Zero disables retries.
+    pub max_retries: u32,
+
+    /// The delay before the first retry.
+    pub initial_delay: Duration,
+
+    /// The longest delay between two attempts.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries failed tiles.
+    #[must_use]
+    pub fn never() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the delay before loading a tile again after `attempts` failed attempts, or `None`
+    /// if the tile should not be retried anymore.
+    #[must_use]
+    pub fn delay(&self, attempts: u32) -> Option<Duration> {
+        if attempts == 0 || attempts > self.max_retries {
+            return None;
+        }
+        let factor = 2_u32.saturating_pow(attempts - 1);
+        Some(
+            self.initial_delay
+                .saturating_mul(factor)
+                .min(self.max_delay),
+        )
+    }
+}
+
+/// Stops the worker threads when the last clone of a pool is dropped.
+struct PoolHandle {
+    shared: Arc<Shared>,
+}
+
+impl Drop for PoolHandle {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.shutdown = true;
+        for job in state.queue.drain(..) {
+            job.ticket.queued.store(false, Ordering::Relaxed);
+            job.sender.send(Err(Arc::new(eyre::eyre!(
+                "The tile download pool was shut down"
+            ))));
+        }
+        self.shared.condvar.notify_all();
+    }
+}
+
+/// A tile download that has been queued in a [`DownloadPool`].
+pub(crate) struct Download {
+    promise: Promise<TileResult>,
+    ticket: Arc<Ticket>,
+    shared: Arc<Shared>,
+}
+
+impl Download {
+    /// Returns the result of the download if it has finished.
+    pub(crate) fn ready(&self) -> Option<&TileResult> {
+        self.promise.ready()
+    }
+
+    /// Blocks until the download has finished and returns its result.
+    pub(crate) fn wait(&self) -> &TileResult {
+        self.promise.block_until_ready()
+    }
+
+    /// Updates the priority of the download if it has not started yet.
+    pub(crate) fn set_priority(&self, priority: f32) {
+        self.ticket
+            .priority
+            .store(priority.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the download is still waiting for a worker.
+    pub(crate) fn is_queued(&self) -> bool {
+        self.ticket.queued.load(Ordering::Relaxed)
+    }
+}
+
+/// Dropping a download removes it from the queue. A download that has already started runs to
+/// completion, and its result is thrown away.
+impl Drop for Download {
+    fn drop(&mut self) {
+        if !self.is_queued() {
+            return;
+        }
+        let mut state = self.shared.lock();
+        if let Some(index) = state
+            .queue
+            .iter()
+            .position(|job| Arc::ptr_eq(&job.ticket, &self.ticket))
+        {
+            state.queue.swap_remove(index);
+        }
+    }
+}
+
+/// The priority of a download, shared between the queue and the [`Download`].
+struct Ticket {
+    /// The bits of an `f32`. Lower values are downloaded first.
+    priority: AtomicU32,
+
+    /// Whether the download is still in the queue. Only cleared while the queue is locked.
+    queued: AtomicBool,
+}
+
+impl Ticket {
+    fn priority(&self) -> f32 {
+        f32::from_bits(self.priority.load(Ordering::Relaxed))
+    }
+}
+
+/// A queued download.
+struct Job {
+    /// The host the tile is downloaded from, or `None` for local files.
+    host: Option<String>,
+    ticket: Arc<Ticket>,
+    task: Task,
+    sender: Sender<TileResult>,
+}
+
+/// The state shared between a pool, its workers and its downloads.
+struct Shared {
+    state: Mutex<State>,
+    condvar: Condvar,
+}
+
+struct State {
+    queue: Vec<Job>,
+    active_per_host: HashMap<String, usize>,
+    max_per_host: usize,
+    shutdown: bool,
+}
+
+impl State {
+    /// Takes the queued job with the lowest priority value whose host has a free slot.
+    fn take_next(&mut self) -> Option<Job> {
+        let index = self
+            .queue
+            .iter()
+            .enumerate()
+            .filter(|(_, job)| {
+                job.host.as_ref().is_none_or(|host| {
+                    self.active_per_host.get(host).copied().unwrap_or(0) < self.max_per_host
+                })
+            })
+            .min_by(|(_, a), (_, b)| a.ticket.priority().total_cmp(&b.ticket.priority()))
+            .map(|(index, _)| index)?;
+        let job = self.queue.swap_remove(index);
+        job.ticket.queued.store(false, Ordering::Relaxed);
+        Some(job)
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The main loop of a worker thread.
+    fn work(&self) {
+        let mut state = self.lock();
+        loop {
+            if state.shutdown {
+                return;
+            }
+            let Some(Job {
+                host, task, sender, ..
+            }) = state.take_next()
+            else {
+                state = self
+                    .condvar
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner);
+                continue;
+            };
+
+            if let Some(host) = &host {
+                *state.active_per_host.entry(host.clone()).or_default() += 1;
+            }
+            drop(state);
+
+            let result = catch_unwind(AssertUnwindSafe(task))
+                .unwrap_or_else(|_| Err(Arc::new(eyre::eyre!("The tile download panicked"))));
+            sender.send(result);
+
+            state = self.lock();
+            if let Some(host) = &host
+                && let Some(active) = state.active_per_host.get_mut(host)
+            {
+                *active -= 1;
+                if *active == 0 {
+                    state.active_per_host.remove(host);
+                }
+            }
+            // A slot for this host was freed, so jobs that were waiting for it may now run.
+            self.condvar.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn retry_policy_backs_off_exponentially() {
+        let policy = RetryPolicy {
+            max_retries: 4,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5),
+        };
+        let delays: Vec<_> = (1..=5).map(|attempts| policy.delay(attempts)).collect();
+        assert_eq!(
+            delays,
+            vec![
+                Some(Duration::from_secs(1)),
+                Some(Duration::from_secs(2)),
+                Some(Duration::from_secs(4)),
+                Some(Duration::from_secs(5)),
+                None,
+            ]
+        );
+        assert_eq!(RetryPolicy::never().delay(1), None);
+    }
+
+    fn ok_result() -> TileResult {
+        Ok(egui::ColorImage::filled([1, 1], egui::Color32::WHITE))
+    }
+
+    fn wait_until_ready(download: &Download) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while download.ready().is_none() {
+            assert!(Instant::now() < deadline, "download did not finish");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn download_pool_runs_closest_tiles_first() {
+        let pool = DownloadPool::new(1);
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let (gate_tx, gate_rx) = std::sync::mpsc::channel::<()>();
+
+        // Keep the only worker busy until every job has been queued.
+        let blocker = pool.spawn(Some("a".to_string()), 0.0, move || {
+            let _ = gate_rx.recv();
+            ok_result()
+        });
+        let downloads: Vec<_> = ["far", "near", "middle"]
+            .into_iter()
+            .zip([3.0, 1.0, 2.0])
+            .map(|(name, priority)| {
+                let order = order.clone();
+                pool.spawn(Some("a".to_string()), priority, move || {
+                    order.lock().unwrap().push(name);
+                    ok_result()
+                })
+            })
+            .collect();
+        // The map moved so that the far tile is now the closest one.
+        downloads[0].set_priority(0.5);
+        gate_tx.send(()).unwrap();
+
+        wait_until_ready(&blocker);
+        downloads.iter().for_each(wait_until_ready);
+        assert_eq!(*order.lock().unwrap(), vec!["far", "near", "middle"]);
+    }
+
+    #[test]
+    fn download_pool_cancels_queued_downloads() {
+        let pool = DownloadPool::new(1);
+        let (gate_tx, gate_rx) = std::sync::mpsc::channel::<()>();
+        let (started_tx, started_rx) = std::sync::mpsc::channel::<()>();
+        let blocker = pool.spawn(Some("a".to_string()), 0.0, move || {
+            let _ = started_tx.send(());
+            let _ = gate_rx.recv();
+            ok_result()
+        });
+        started_rx.recv().unwrap();
+        let ran = Arc::new(AtomicUsize::new(0));
+        let ran_clone = ran.clone();
+        let queued = pool.spawn(Some("a".to_string()), 1.0, move || {
+            ran_clone.fetch_add(1, Ordering::SeqCst);
+            ok_result()
+        });
+
+        assert!(queued.is_queued());
+        drop(queued);
+        assert_eq!(pool.queued(), 0);
+        gate_tx.send(()).unwrap();
+        wait_until_ready(&blocker);
+        assert!(!blocker.is_queued());
+        assert_eq!(ran.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn download_pool_limits_downloads_per_host() {
+        let pool = DownloadPool::with_limits(4, 1);
+        let active = Arc::new(AtomicUsize::new(0));
+        let max_active = Arc::new(AtomicUsize::new(0));
+
+        let downloads: Vec<_> = (0..4)
+            .map(|i| {
+                let active = active.clone();
+                let max_active = max_active.clone();
+                pool.spawn(Some("a".to_string()), i as f32, move || {
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_active.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(10));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    ok_result()
+                })
+            })
+            .collect();
+
+        downloads.iter().for_each(wait_until_ready);
+        assert_eq!(max_active.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn download_pool_does_not_limit_local_files() {
+        let pool = DownloadPool::with_limits(2, 1);
+        let (started_tx, started_rx) = std::sync::mpsc::channel::<()>();
+        let (gate_tx, gate_rx) = std::sync::mpsc::channel::<()>();
+        let gate_rx = Arc::new(Mutex::new(gate_rx));
+
+        let downloads: Vec<_> = (0..2)
+            .map(|i| {
+                let started_tx = started_tx.clone();
+                let gate_rx = gate_rx.clone();
+                pool.spawn(None, i as f32, move || {
+                    let _ = started_tx.send(());
+                    let _ = gate_rx.lock().unwrap().recv();
+                    ok_result()
+                })
+            })
+            .collect();
+
+        // Both tasks start even though the pool allows a single download per host.
+        for _ in 0..2 {
+            started_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+        assert!(downloads.iter().all(|download| !download.is_queued()));
+        gate_tx.send(()).unwrap();
+        gate_tx.send(()).unwrap();
+        downloads.iter().for_each(wait_until_ready);
+    }
+}
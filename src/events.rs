@@ -0,0 +1,196 @@
+//! Events of the map widget.
+//!
+//! A map with [`Map::with_events`](crate::Map::with_events) collects what happens to it while it
+//! is shown, so that an application can react to interactions without checking the response and
+//! the map state every frame.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use egui_map_view::{Map, config::OpenStreetMapConfig, events::MapEvent};
+//!
+//! let map = Map::new(OpenStreetMapConfig::default()).with_events(true);
+//!
+//! fn show_map(ui: &mut egui::Ui, map: &mut Map) {
+//!     ui.add(&mut *map);
+//!     for event in map.take_events() {
+//!         match event {
+//!             MapEvent::Clicked { pos, .. } => println!("Clicked at {}, {}", pos.lon, pos.lat),
+//!             MapEvent::LayerFeatureClicked { layer_key, feature, .. } => {
+//!                 println!("Clicked {feature} on layer {layer_key}");
+//!             }
+//!             _ => {}
+//!         }
+//!     }
+//! }
+//! ```
+
+use egui::PointerButton;
+
+use crate::projection::GeoPos;
+
+/// Something that happened to the map.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MapEvent {
+    /// The center, zoom level or bearing of the map changed, by user input, an animation or the
+    /// application. Changes during a single frame are reported once, with the new view.
+    ViewChanged {
+        /// The new center of the map.
+        center: GeoPos,
+
+        /// The new zoom level.
+        zoom: f64,
+
+        /// The new bearing, in degrees clockwise from north.
+        bearing: f64,
+    },
+
+    /// The map was clicked.
+    Clicked {
+        /// Where the map was clicked.
+        pos: GeoPos,
+
+        /// The button that was clicked.
+        button: PointerButton,
+    },
+
+    /// The map was double-clicked with the primary button.
+    DoubleClicked {
+        /// Where the map was double-clicked.
+        pos: GeoPos,
+    },
+
+    /// The map was pressed and held still on a touch screen.
+    LongPressed {
+        /// Where the map was pressed.
+        pos: GeoPos,
+    },
+
+    /// The user started dragging the map.
+    DragStarted {
+        /// Where the drag started.
+        pos: GeoPos,
+    },
+
+    /// The user stopped dragging the map.
+    DragEnded {
+        /// Where the drag ended.
+        pos: GeoPos,
+    },
+
+    /// The pointer moved onto the map.
+    HoverEnter {
+        /// Where the pointer entered the map.
+        pos: GeoPos,
+    },
+
+    /// The pointer left the map.
+    HoverLeave,
+
+    /// A feature of a layer was clicked.
+    LayerFeatureClicked {
+        /// The key of the layer in the map.
+        layer_key: String,
+
+        /// The feature that was clicked, as identified by the layer. For an `SvgLayer` this is
+        /// the metadata of the element.
+        feature: String,
+
+        /// Where the feature was clicked.
+        pos: GeoPos,
+
+        /// The button that was clicked.
+        button: PointerButton,
+    },
+}
+
+/// A click on a feature of a layer, reported by [`Layer::take_feature_clicks`].
+///
+/// [`Layer::take_feature_clicks`]: crate::layers::Layer::take_feature_clicks
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeatureClick {
+    /// The feature that was clicked, as identified by the layer.
+    pub feature: String,
+
+    /// Where the feature was clicked.
+    pub pos: GeoPos,
+
+    /// The button that was clicked.
+    pub button: PointerButton,
+}
+
+/// Collects the events of a map until the application takes them.
+#[derive(Debug, Default)]
+pub(crate) struct EventQueue {
+    events: Vec<MapEvent>,
+
+    /// Whether events are collected at all.
+    enabled: bool,
+}
+
+impl EventQueue {
+    /// Starts or stops collecting events. Stopping drops the events that were not taken.
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.events.clear();
+        }
+    }
+
+    /// Returns `true` if events are collected.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Adds an event, if events are collected. A view change replaces a view change right before
+    /// it, so that the queue does not grow every frame of an animation.
+    pub(crate) fn push(&mut self, event: MapEvent) {
+        if !self.enabled {
+            return;
+        }
+        if matches!(event, MapEvent::ViewChanged { .. })
+            && let Some(last @ MapEvent::ViewChanged { .. }) = self.events.last_mut()
+        {
+            *last = event;
+            return;
+        }
+        self.events.push(event);
+    }
+
+    /// Takes all the events, leaving the queue empty.
+    pub(crate) fn take(&mut self) -> Vec<MapEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_queue_merges_view_changes() {
+        let view = |zoom| MapEvent::ViewChanged {
+            center: GeoPos::from((0.0, 0.0)),
+            zoom,
+            bearing: 0.0,
+        };
+        let mut queue = EventQueue::default();
+        queue.push(view(1.0));
+        assert!(queue.take().is_empty());
+
+        queue.set_enabled(true);
+        queue.push(view(1.0));
+        queue.push(view(2.0));
+        queue.push(MapEvent::HoverLeave);
+        queue.push(view(3.0));
+        assert_eq!(
+            queue.take(),
+            vec![view(2.0), MapEvent::HoverLeave, view(3.0)]
+        );
+        assert!(queue.take().is_empty());
+
+        queue.push(MapEvent::HoverLeave);
+        queue.set_enabled(false);
+        assert!(queue.take().is_empty());
+    }
+}
@@ -0,0 +1,210 @@
+//! Settings of the HTTP client that downloads map tiles.
+//!
+//! By default all tiles are downloaded with a shared client that only sets a `User-Agent`. Tile
+//! providers that need authentication, custom headers, timeouts or a proxy can build an
+//! [`HttpClient`] and return it from
+//! [`MapConfig::http_client`](crate::config::MapConfig::http_client), or set it on a single map
+//! with [`Map::with_http_client`](crate::Map::with_http_client).
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use egui_map_view::{Map, config::OpenStreetMapConfig, http::HttpClient};
+//!
+//! let client = HttpClient::builder()
+//!     .user_agent("my-app/1.0")
+//!     .referer("https://example.com/")
+//!     .bearer_token("secret")
+//!     .timeout(Duration::from_secs(10))
+//!     .proxy("http://proxy.example.com:8080")
+//!     .build()
+//!     .expect("Invalid HTTP client settings");
+//! let map = Map::new(OpenStreetMapConfig::default()).with_http_client(client);
+//! ```
+
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
+
+use crate::MapError;
+
+// Reuse the reqwest client for all tile downloads by making it a static variable.
+static DEFAULT_CLIENT: LazyLock<reqwest::blocking::Client> = LazyLock::new(|| {
+    reqwest::blocking::Client::builder()
+        .user_agent(default_user_agent())
+        .build()
+        .expect("Failed to build reqwest client")
+});
+
+/// Returns the `User-Agent` that identifies this crate.
+fn default_user_agent() -> String {
+    format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+/// An HTTP client that downloads map tiles.
+///
+/// The client is cheap to clone, and clones share their connection pool.
+#[derive(Clone, Debug)]
+pub struct HttpClient {
+    client: reqwest::blocking::Client,
+}
+
+impl Default for HttpClient {
+    /// Returns the shared client that is used when no other client has been set.
+    fn default() -> Self {
+        Self {
+            client: DEFAULT_CLIENT.clone(),
+        }
+    }
+}
+
+impl From<reqwest::blocking::Client> for HttpClient {
+    fn from(client: reqwest::blocking::Client) -> Self {
+        Self { client }
+    }
+}
+
+impl HttpClient {
+    /// Returns a builder for a client with custom settings.
+    #[must_use]
+    pub fn builder() -> HttpClientBuilder {
+        HttpClientBuilder::default()
+    }
+
+    /// Returns the underlying `reqwest` client.
+    pub(crate) fn client(&self) -> &reqwest::blocking::Client {
+        &self.client
+    }
+}
+
+/// Settings for an [`HttpClient`].
+#[derive(Clone, Debug, Default)]
+pub struct HttpClientBuilder {
+    user_agent: Option<String>,
+    headers: Vec<(String, String, bool)>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    proxy: Option<String>,
+}
+
+impl HttpClientBuilder {
+    /// Sets the `User-Agent` header. Defaults to the name and version of this crate.
+    #[must_use]
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Adds a header that is sent with every tile request.
+    #[must_use]
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into(), false));
+        self
+    }
+
+    /// Sets the `Referer` header, which some tile providers use to check where their tiles are
+    /// shown.
+    #[must_use]
+    pub fn referer(self, referer: impl Into<String>) -> Self {
+        self.header(header::REFERER.as_str(), referer)
+    }
+
+    /// Sets an `Authorization: Bearer` header. The header is marked as sensitive, so that the
+    /// token is left out of debug output.
+    #[must_use]
+    pub fn bearer_token(mut self, token: impl AsRef<str>) -> Self {
+        self.headers.push((
+            header::AUTHORIZATION.as_str().to_string(),
+            format!("Bearer {}", token.as_ref()),
+            true,
+        ));
+        self
+    }
+
+    /// Sets how long a tile request may take in total, from connecting to reading the whole
+    /// response. Defaults to 30 seconds.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long connecting to the tile server may take.
+    #[must_use]
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Sends all requests through a proxy, for example `http://proxy.example.com:8080`. Without
+    /// a proxy, the `HTTP_PROXY` and `HTTPS_PROXY` environment variables are honoured.
+    #[must_use]
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// Builds the client.
+    ///
+    /// Fails if a header or the proxy URL is invalid.
+    pub fn build(self) -> Result<HttpClient, MapError> {
+        let mut headers = HeaderMap::new();
+        for (name, value, sensitive) in self.headers {
+            let name = HeaderName::try_from(name.as_str())
+                .map_err(|e| MapError::HttpClientError(format!("Invalid header {name}: {e}")))?;
+            let mut value = HeaderValue::try_from(value).map_err(|e| {
+                MapError::HttpClientError(format!("Invalid value for header {name}: {e}"))
+            })?;
+            value.set_sensitive(sensitive);
+            headers.append(name, value);
+        }
+
+        let mut builder = reqwest::blocking::Client::builder()
+            .user_agent(self.user_agent.unwrap_or_else(default_user_agent))
+            .default_headers(headers);
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(proxy) = self.proxy {
+            let proxy = reqwest::Proxy::all(&proxy)
+                .map_err(|e| MapError::HttpClientError(format!("Invalid proxy {proxy}: {e}")))?;
+            builder = builder.proxy(proxy);
+        }
+
+        let client = builder
+            .build()
+            .map_err(|e| MapError::HttpClientError(e.to_string()))?;
+        Ok(HttpClient { client })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn http_client_builder_validates_settings() {
+        let client = HttpClient::builder()
+            .user_agent("test/1.0")
+            .referer("https://example.com/")
+            .bearer_token("secret")
+            .header("X-Api-Key", "key")
+            .timeout(Duration::from_secs(5))
+            .proxy("http://proxy.example.com:8080")
+            .build();
+        assert!(client.is_ok());
+
+        let invalid_header = HttpClient::builder().header("Bad Header", "value").build();
+        assert!(matches!(invalid_header, Err(MapError::HttpClientError(_))));
+
+        let invalid_value = HttpClient::builder()
+            .header("X-Api-Key", "line\nbreak")
+            .build();
+        assert!(matches!(invalid_value, Err(MapError::HttpClientError(_))));
+    }
+}
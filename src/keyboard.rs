@@ -0,0 +1,168 @@
+//! Keyboard navigation of the map.
+//!
+//! The map takes keyboard focus when it is clicked or reached with the Tab key. While it has
+//! focus, the arrow keys pan the map, `+` and `-` zoom it and Home moves it back to the default
+//! view of the map configuration. The keys can be changed with
+//! [`Map::with_key_bindings`](crate::Map::with_key_bindings).
+//!
+//! # Example
+//!
+//! ```
+//! use egui::Key;
+//! use egui_map_view::{Map, config::OpenStreetMapConfig, keyboard::KeyBindings};
+//!
+//! // Zoom with Page Up and Page Down instead of plus and minus.
+//! let bindings = KeyBindings {
+//!     zoom_in: vec![Key::PageUp],
+//!     zoom_out: vec![Key::PageDown],
+//!     ..KeyBindings::default()
+//! };
+//! let map = Map::new(OpenStreetMapConfig::default()).with_key_bindings(bindings);
+//! ```
+
+use egui::{InputState, Key, Vec2};
+
+/// The keys that move the map while it has keyboard focus.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBindings {
+    /// Keys that move the view up, which is north unless the map is rotated.
+    pub pan_up: Vec<Key>,
+
+    /// Keys that move the view down.
+    pub pan_down: Vec<Key>,
+
+    /// Keys that move the view left.
+    pub pan_left: Vec<Key>,
+
+    /// Keys that move the view right.
+    pub pan_right: Vec<Key>,
+
+    /// Keys that zoom in by one level.
+    pub zoom_in: Vec<Key>,
+
+    /// Keys that zoom out by one level.
+    pub zoom_out: Vec<Key>,
+
+    /// Keys that move the map back to its default center and zoom level.
+    pub reset: Vec<Key>,
+
+    /// How fast the view moves while a pan key is held down, in points per second.
+    pub pan_speed: f32,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            pan_up: vec![Key::ArrowUp],
+            pan_down: vec![Key::ArrowDown],
+            pan_left: vec![Key::ArrowLeft],
+            pan_right: vec![Key::ArrowRight],
+            // Plus is typed with Shift and the equals key on many keyboards.
+            zoom_in: vec![Key::Plus, Key::Equals],
+            zoom_out: vec![Key::Minus],
+            reset: vec![Key::Home],
+            pan_speed: 500.0,
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Bindings without any keys, which turn keyboard navigation off.
+    #[must_use]
+    pub fn disabled() -> Self {
+        Self {
+            pan_up: Vec::new(),
+            pan_down: Vec::new(),
+            pan_left: Vec::new(),
+            pan_right: Vec::new(),
+            zoom_in: Vec::new(),
+            zoom_out: Vec::new(),
+            reset: Vec::new(),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the direction the view moves in because of the pan keys that are held down.
+    pub(crate) fn pan_direction(&self, input: &InputState) -> Vec2 {
+        let down = |keys: &[Key]| keys.iter().any(|key| input.key_down(*key));
+        let axis = |negative: bool, positive: bool| match (negative, positive) {
+            (true, false) => -1.0,
+            (false, true) => 1.0,
+            _ => 0.0,
+        };
+        Vec2::new(
+            axis(down(&self.pan_left), down(&self.pan_right)),
+            axis(down(&self.pan_up), down(&self.pan_down)),
+        )
+    }
+
+    /// Returns how many levels to zoom in, or out if negative, because of the zoom keys pressed
+    /// since the last frame.
+    pub(crate) fn zoom_steps(&self, input: &InputState) -> i32 {
+        let presses = |keys: &[Key]| {
+            keys.iter()
+                .map(|key| input.num_presses(*key))
+                .sum::<usize>()
+        };
+        presses(&self.zoom_in) as i32 - presses(&self.zoom_out) as i32
+    }
+
+    /// Returns `true` if a reset key was pressed since the last frame.
+    pub(crate) fn reset_pressed(&self, input: &InputState) -> bool {
+        self.reset.iter().any(|key| input.key_pressed(*key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::{Context, Event, Modifiers, RawInput};
+
+    /// Runs a frame with the given keys pressed and calls `check` with its input.
+    fn with_keys_pressed(keys: &[Key], check: impl FnOnce(&InputState)) {
+        let ctx = Context::default();
+        let events = keys
+            .iter()
+            .map(|key| Event::Key {
+                key: *key,
+                physical_key: None,
+                pressed: true,
+                repeat: false,
+                modifiers: Modifiers::NONE,
+            })
+            .collect();
+        let mut check = Some(check);
+        let _ = ctx.run_ui(
+            RawInput {
+                events,
+                ..RawInput::default()
+            },
+            |ui| {
+                if let Some(check) = check.take() {
+                    ui.input(check);
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn key_bindings_read_input() {
+        let bindings = KeyBindings::default();
+        with_keys_pressed(&[Key::ArrowUp, Key::ArrowRight, Key::Plus], |input| {
+            assert_eq!(bindings.pan_direction(input), Vec2::new(1.0, -1.0));
+            assert_eq!(bindings.zoom_steps(input), 1);
+            assert!(!bindings.reset_pressed(input));
+        });
+        with_keys_pressed(&[Key::ArrowLeft, Key::ArrowRight, Key::Home], |input| {
+            assert_eq!(bindings.pan_direction(input), Vec2::ZERO);
+            assert!(bindings.reset_pressed(input));
+        });
+
+        let disabled = KeyBindings::disabled();
+        with_keys_pressed(&[Key::ArrowUp, Key::Minus, Key::Home], |input| {
+            assert_eq!(disabled.pan_direction(input), Vec2::ZERO);
+            assert_eq!(disabled.zoom_steps(input), 0);
+            assert!(!disabled.reset_pressed(input));
+        });
+    }
+}
@@ -29,8 +29,9 @@ impl AreaLayer {
                 && let Some(area) = self.areas.get_mut(area_idx)
                 && let AreaShape::Polygon(points) = &mut area.shape
             {
-                let p1_screen = projection.project(points[node_idx]);
-                let p2_screen = projection.project(points[(node_idx + 1) % points.len()]);
+                let screen_points = projection.project_path(points);
+                let p1_screen = screen_points[node_idx];
+                let p2_screen = screen_points[(node_idx + 1) % points.len()];
 
                 let t = projection_factor(pointer_pos, p1_screen, p2_screen);
 
@@ -443,6 +444,29 @@ impl AreaLayer {
         None
     }
 
+    /// Finds the topmost area whose fill, handles or outline are at `screen_pos`.
+    pub(crate) fn find_area_at(
+        &self,
+        screen_pos: Pos2,
+        projection: &MapProjection,
+        limit_to_area: Option<usize>,
+    ) -> Option<usize> {
+        self.areas.iter().enumerate().rev().find_map(|(idx, area)| {
+            if limit_to_area.is_some_and(|limit_idx| limit_idx != idx) {
+                return None;
+            }
+            let contains_fill = area.contains(screen_pos, projection);
+            let over_handle = self.find_object_at(screen_pos, projection, Some(idx)).is_some();
+            let over_segment = self.find_line_segment_at(screen_pos, projection, Some(idx)).is_some();
+
+            if contains_fill || over_handle || over_segment {
+                Some(idx)
+            } else {
+                None
+            }
+        })
+    }
+
     pub(crate) fn find_node_at(
         &self,
         screen_pos: Pos2,
@@ -476,9 +500,10 @@ impl AreaLayer {
                 if points.len() < 2 {
                     continue;
                 }
+                let screen_points = projection.project_path(points);
                 for i in 0..points.len() {
-                    let p1 = projection.project(points[i]);
-                    let p2 = projection.project(points[(i + 1) % points.len()]);
+                    let p1 = screen_points[i];
+                    let p2 = screen_points[(i + 1) % points.len()];
 
                     if dist_sq_to_segment(screen_pos, p1, p2) < click_tolerance {
                         return Some((area_idx, i));
@@ -511,7 +536,7 @@ impl AreaLayer {
         if points.len() < 3 {
             return true;
         }
-        let screen_points: Vec<Pos2> = points.iter().map(|p| projection.project(*p)).collect();
+        let screen_points: Vec<Pos2> = projection.project_path(points);
 
         let n = screen_points.len();
         let prev_node_idx = (node_idx + n - 1) % n;
@@ -1,10 +1,10 @@
 use crate::layers::{default_opacity, Layer};
-use crate::projection::MapProjection;
-use egui::{Color32, Painter, Response};
+use crate::projection::{GeoPos, MapProjection};
+use egui::{Color32, Painter, Response, Ui};
 use serde::{Deserialize, Serialize};
 use std::any::Any;
 
-use super::types::{Area, AreaMode, DraggedObject};
+use super::types::{Area, AreaMode, AreaShape, DraggedObject};
 
 /// Layer implementation that allows the user to draw polygons on the map.
 #[derive(Clone, Serialize, Deserialize)]
@@ -76,6 +76,30 @@ impl AreaLayer {
     pub fn areas_mut(&mut self) -> &mut Vec<Area> {
         &mut self.areas
     }
+
+    /// Removes an area from the layer, keeping the selection on the same area.
+    pub fn remove_area(&mut self, index: usize) -> Option<Area> {
+        if index >= self.areas.len() {
+            return None;
+        }
+        self.selected_area = match self.selected_area {
+            Some(selected) if selected == index => None,
+            Some(selected) if selected > index => Some(selected - 1),
+            selected => selected,
+        };
+        self.dragged_object = None;
+        self.hovered_object = None;
+        Some(self.areas.remove(index))
+    }
+
+    /// The area that can be edited in the current mode, if only one can be.
+    fn editable_area(&self) -> Option<Option<usize>> {
+        match self.mode {
+            AreaMode::Disabled => None,
+            AreaMode::Modify => Some(None),
+            AreaMode::ModifySelected => self.selected_area.map(Some),
+        }
+    }
 }
 
 impl Layer for AreaLayer {
@@ -107,18 +131,7 @@ impl Layer for AreaLayer {
                     && let Some(pointer_pos) = response.interact_pointer_pos()
                 {
                     // Find if any area was clicked to select it.
-                    let clicked_area_idx =
-                        self.areas.iter().enumerate().rev().find_map(|(idx, area)| {
-                            let contains_fill = area.contains(pointer_pos, projection);
-                            let over_handle = self.find_object_at(pointer_pos, projection, Some(idx)).is_some();
-                            let over_segment = self.find_line_segment_at(pointer_pos, projection, Some(idx)).is_some();
-
-                            if contains_fill || over_handle || over_segment {
-                                Some(idx)
-                            } else {
-                                None
-                            }
-                        });
+                    let clicked_area_idx = self.find_area_at(pointer_pos, projection, None);
 
                     if clicked_area_idx != self.selected_area {
                         self.selected_area = clicked_area_idx;
@@ -138,4 +151,28 @@ impl Layer for AreaLayer {
     fn draw(&self, painter: &Painter, projection: &MapProjection) {
         self.draw_layer(painter, projection);
     }
+
+    fn context_menu(&mut self, ui: &mut Ui, pos: GeoPos, projection: &MapProjection) {
+        let Some(limit_to_area) = self.editable_area() else {
+            return;
+        };
+        let screen_pos = projection.project(pos);
+
+        if let Some((area_idx, node_idx)) = self.find_node_at(screen_pos, projection, limit_to_area)
+            && let AreaShape::Polygon(points) = &mut self.areas[area_idx].shape
+            // A polygon needs at least three nodes.
+            && points.len() > 3
+            && ui.button("Delete node").clicked()
+        {
+            points.remove(node_idx);
+            self.dragged_object = None;
+            self.hovered_object = None;
+        }
+
+        if let Some(area_idx) = self.find_area_at(screen_pos, projection, limit_to_area)
+            && ui.button("Delete area").clicked()
+        {
+            self.remove_area(area_idx);
+        }
+    }
 }
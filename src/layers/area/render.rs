@@ -10,7 +10,7 @@ impl AreaLayer {
     pub(crate) fn draw_layer(&self, painter: &Painter, projection: &MapProjection) {
         for (area_idx, area) in self.areas.iter().enumerate() {
             let points = area.get_points(projection);
-            let screen_points: Vec<Pos2> = points.iter().map(|p| projection.project(*p)).collect();
+            let screen_points: Vec<Pos2> = projection.project_path(&points);
 
             // Draw polygon outline
             if screen_points.len() >= 3 {
@@ -7,7 +7,7 @@ use super::types::{Area, AreaMode, AreaShape, DraggedObject};
 // Helper for creating a dummy projection for tests
 fn dummy_projection() -> MapProjection {
     MapProjection::new(
-        10,                // zoom
+        10.0,              // zoom
         (0.0, 0.0).into(), // center
         Rect::from_min_size(Pos2::ZERO, vec2(1000.0, 1000.0)),
     )
@@ -40,6 +40,34 @@ fn area_layer_add_area() {
     assert_eq!(layer.areas.len(), 1);
 }
 
+#[test]
+fn area_layer_remove_area() {
+    let mut layer = AreaLayer::default();
+    for lon in [0.0, 10.0, 20.0] {
+        layer.add_area(Area {
+            shape: AreaShape::Circle {
+                center: (lon, 0.0).into(),
+                radius: 1000.0,
+                points: None,
+            },
+            stroke: Default::default(),
+            fill: Default::default(),
+            fill_type: Default::default(),
+        });
+    }
+    layer.selected_area = Some(2);
+
+    // The selection follows the selected area when an area before it is removed.
+    assert!(layer.remove_area(0).is_some());
+    assert_eq!(layer.areas.len(), 2);
+    assert_eq!(layer.selected_area, Some(1));
+
+    // And is cleared when the selected area is removed.
+    assert!(layer.remove_area(1).is_some());
+    assert_eq!(layer.selected_area, None);
+    assert!(layer.remove_area(5).is_none());
+}
+
 #[test]
 fn circle_get_points_with_fixed_number() {
     let projection = dummy_projection();
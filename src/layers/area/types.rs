@@ -106,7 +106,7 @@ impl Area {
     /// Checks if the area can be successfully triangulated.
     pub(crate) fn can_triangulate(&self, projection: &MapProjection) -> bool {
         let points = self.get_points(projection);
-        let screen_points: Vec<Pos2> = points.iter().map(|p| projection.project(*p)).collect();
+        let screen_points: Vec<Pos2> = projection.project_path(&points);
 
         if screen_points.len() < 3 {
             return true;
@@ -266,8 +266,7 @@ impl Area {
             }
             AreaShape::Polygon(_) => {
                 let points = self.get_points(projection);
-                let screen_points: Vec<Pos2> =
-                    points.iter().map(|p| projection.project(*p)).collect();
+                let screen_points: Vec<Pos2> = projection.project_path(&points);
                 if screen_points.len() < 3 {
                     return false;
                 }
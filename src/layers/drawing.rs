@@ -329,8 +329,7 @@ impl Layer for DrawingLayer {
     fn draw(&self, painter: &Painter, projection: &MapProjection) {
         for polyline in &self.polylines {
             if polyline.0.len() > 1 {
-                let screen_points: Vec<egui::Pos2> =
-                    polyline.0.iter().map(|p| projection.project(*p)).collect();
+                let screen_points = projection.project_path(&polyline.0);
                 painter.add(egui::Shape::line(
                     screen_points,
                     Stroke {
@@ -354,7 +353,7 @@ fn split_polyline_by_erase_circle(
         return vec![];
     }
 
-    let screen_points: Vec<Pos2> = polyline.iter().map(|p| projection.project(*p)).collect();
+    let screen_points = projection.project_path(polyline);
 
     let mut new_polylines = Vec::new();
     let mut current_line = Vec::new();
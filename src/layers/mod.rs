@@ -1,9 +1,10 @@
 //! Layers for the map view that can handle input, and draw on top of the map view different kinds of data.
 //!
-use egui::{Painter, Pos2, Response};
+use egui::{Painter, Pos2, Response, Ui};
 use std::any::Any;
 
-use crate::projection::MapProjection;
+use crate::events::FeatureClick;
+use crate::projection::{GeoPos, MapProjection};
 
 /// GeoJSON serialization and deserialization for layers.
 #[cfg(feature = "geojson")]
@@ -110,6 +111,16 @@ pub trait Layer: Any {
 
     /// Sets the opacity of the layer.
     fn set_opacity(&mut self, _opacity: f32) {}
+
+    /// Takes the clicks on features of the layer since the last call. The map reports them as
+    /// [`MapEvent::LayerFeatureClicked`](crate::events::MapEvent::LayerFeatureClicked).
+    fn take_feature_clicks(&mut self) -> Vec<FeatureClick> {
+        Vec::new()
+    }
+
+    /// Adds the entries of the layer to the context menu of the map, which was opened with a
+    /// secondary click at `pos`. Entries usually act on the feature under `pos`, if any.
+    fn context_menu(&mut self, _ui: &mut Ui, _pos: GeoPos, _projection: &MapProjection) {}
 }
 
 /// A helper function for `serde` to provide a default opacity of 1.0.
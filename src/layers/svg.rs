@@ -1,5 +1,6 @@
 //! A layer for placing SVG elements on the map.
 
+use crate::events::FeatureClick;
 use crate::layers::{Layer, default_opacity};
 use crate::projection::{GeoPos, MapProjection};
 use egui::{Color32, Painter, PointerButton, Pos2, Response};
@@ -136,6 +137,10 @@ pub struct SvgLayer {
     #[serde(skip)]
     pub events: Vec<SvgClickEvent>,
 
+    /// Clicks on the SVG elements that the map has not taken yet.
+    #[serde(skip)]
+    feature_clicks: Vec<FeatureClick>,
+
     /// The index of the element currently being dragged.
     #[serde(skip)]
     pub dragging_index: Option<usize>,
@@ -150,6 +155,7 @@ impl Default for SvgLayer {
         Self {
             elements: Vec::new(),
             events: Vec::new(),
+            feature_clicks: Vec::new(),
             dragging_index: None,
             opacity: 1.0,
         }
@@ -190,6 +196,10 @@ impl Layer for SvgLayer {
         self.opacity = opacity;
     }
 
+    fn take_feature_clicks(&mut self) -> Vec<FeatureClick> {
+        std::mem::take(&mut self.feature_clicks)
+    }
+
     fn handle_input(&mut self, response: &Response, projection: &MapProjection) -> bool {
         // Ensure image loaders are installed
         egui_extras::install_image_loaders(&response.ctx);
@@ -237,7 +247,7 @@ impl Layer for SvgLayer {
 
                     if element.scalable {
                         // Scale the size based on the zoom level.
-                        let scale = 2.0_f32.powi(i32::from(projection.zoom) - 10);
+                        let scale = 2.0_f32.powf(projection.zoom as f32 - 10.0);
                         size *= scale;
                     }
 
@@ -261,12 +271,18 @@ impl Layer for SvgLayer {
                                 PointerButton::Primary
                             };
 
+                            let world_pos = projection.unproject(pointer_pos);
                             self.events.push(SvgClickEvent {
                                 button,
                                 metadata: element.metadata.clone(),
-                                world_pos: projection.unproject(pointer_pos),
+                                world_pos,
                                 screen_pos: pointer_pos,
                             });
+                            self.feature_clicks.push(FeatureClick {
+                                feature: element.metadata.clone(),
+                                pos: world_pos,
+                                button,
+                            });
                             handled = true;
                         }
                     }
@@ -293,7 +309,7 @@ impl Layer for SvgLayer {
                     if element.scalable {
                         // Scale the size based on the zoom level.
                         // We use zoom level 10 as a reference where scale is 1.0.
-                        let scale = 2.0_f32.powi(i32::from(projection.zoom) - 10);
+                        let scale = 2.0_f32.powf(projection.zoom as f32 - 10.0);
                         size *= scale;
                     }
 
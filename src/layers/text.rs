@@ -2,7 +2,7 @@
 
 use crate::layers::{Layer, default_opacity, serde_color32};
 use crate::projection::{GeoPos, MapProjection};
-use egui::{Align2, Color32, FontId, Painter, Pos2, Rect, Response};
+use egui::{Align2, Color32, FontId, Painter, Pos2, Rect, Response, Ui};
 use serde::{Deserialize, Serialize};
 use std::any::Any;
 
@@ -299,6 +299,21 @@ impl Layer for TextLayer {
         }
     }
 
+    fn context_menu(&mut self, ui: &mut Ui, pos: GeoPos, projection: &MapProjection) {
+        if self.mode != TextLayerMode::Modify || self.editing.is_some() {
+            return;
+        }
+        let screen_pos = projection.project(pos);
+        if let Some(index) = self.find_text_at(screen_pos, projection, ui.ctx()) {
+            if ui.button("Edit text").clicked() {
+                self.start_editing(index);
+            }
+            if ui.button("Delete text").clicked() {
+                self.delete(index);
+            }
+        }
+    }
+
     fn draw(&self, painter: &Painter, projection: &MapProjection) {
         for text in &self.texts {
             let screen_pos = projection.project(text.pos);
@@ -1,22 +1,33 @@
 //! A layer for tile maps on the map.
 
 use egui::{Color32, Painter, Response};
-use std::{any::Any, collections::HashMap};
+use std::any::Any;
 
 use crate::{
-    Tile, TileId, config::MapConfig, draw_tile, layers::Layer, load_tile,
-    projection::MapProjection, visible_tiles,
+    TileId, TileLoader, ViewRotation,
+    cache::{DiskCache, MemoryCache, MemoryCacheStats, MemoryLimit},
+    config::MapConfig,
+    download::{DownloadPool, RetryPolicy},
+    draw_tile,
+    http::HttpClient,
+    layers::Layer,
+    load_tile,
+    placeholder::{self, Placeholder, PlaceholderFn},
+    projection::{GeoBounds, MapProjection},
+    show_tile_error, tile_bounds, tile_priority, visible_tiles,
 };
 
 /// A layer that manages and renders map tiles on the map view.
 pub struct TileLayer {
-    tiles: HashMap<TileId, Tile>,
-    visible_tiles: Vec<(TileId, egui::Pos2)>,
+    tiles: MemoryCache,
+    visible_tiles: Vec<(TileId, egui::Rect)>,
     /// Color tint applied to the tile images when rendering
     pub tint: Color32,
     config: Box<dyn MapConfig>,
     /// The opacity of the layer.
     pub opacity: f32,
+    loader: TileLoader,
+    placeholder: Box<PlaceholderFn>,
 }
 
 impl TileLayer {
@@ -28,8 +39,91 @@ impl TileLayer {
             tint: Color32::WHITE,
             config: Box::new(config),
             opacity: 1.0,
+            loader: TileLoader::default(),
+            placeholder: Box::new(placeholder::draw_default),
         }
     }
+
+    /// Sets a persistent cache that is consulted before downloading tiles, and where downloaded
+    /// tiles are stored.
+    #[must_use]
+    pub fn with_disk_cache(mut self, disk_cache: DiskCache) -> Self {
+        self.loader.disk_cache = Some(disk_cache);
+        self
+    }
+
+    /// Sets the pool of worker threads that download the tiles of this layer. By default, all maps
+    /// and tile layers share [`DownloadPool::global`].
+    #[must_use]
+    pub fn with_download_pool(mut self, download_pool: DownloadPool) -> Self {
+        self.loader.download_pool = download_pool;
+        self
+    }
+
+    /// Sets the HTTP client that downloads the tiles of this layer. See
+    /// [`Map::with_http_client`](crate::Map::with_http_client).
+    #[must_use]
+    pub fn with_http_client(mut self, http_client: HttpClient) -> Self {
+        self.loader.http_client = Some(http_client);
+        self
+    }
+
+    /// Sets how tiles of this layer that are loading or failed to load are drawn. See
+    /// [`Map::with_placeholder`](crate::Map::with_placeholder).
+    #[must_use]
+    pub fn with_placeholder(
+        mut self,
+        placeholder: impl Fn(&Painter, &Placeholder<'_>) + 'static,
+    ) -> Self {
+        self.placeholder = Box::new(placeholder);
+        self
+    }
+
+    /// Sets whether the layer reads its tiles only from the disk cache. See
+    /// [`Map::with_offline`](crate::Map::with_offline).
+    #[must_use]
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.loader.offline = offline;
+        self
+    }
+
+    /// Switches the layer between online and offline mode.
+    pub fn set_offline(&mut self, offline: bool) {
+        self.loader.offline = offline;
+    }
+
+    /// Sets how many tiles of this layer are kept in memory. Tiles that are not visible are
+    /// evicted, least recently used first, when the limit is exceeded.
+    #[must_use]
+    pub fn with_memory_limit(mut self, limit: MemoryLimit) -> Self {
+        self.tiles.set_limit(limit);
+        self
+    }
+
+    /// Returns the usage statistics of the in-memory tile cache of this layer.
+    #[must_use]
+    pub fn memory_cache_stats(&self) -> MemoryCacheStats {
+        self.tiles.stats()
+    }
+
+    /// Sets how tiles of this layer that failed with a transient error are retried.
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.loader.retry_policy = retry_policy;
+        self
+    }
+
+    /// Loads every tile of this layer again. See [`Map::reload_tiles`](crate::Map::reload_tiles).
+    pub fn reload_tiles(&mut self) {
+        self.tiles.invalidate(|_| true);
+    }
+
+    /// Loads the tiles of this layer that overlap the bounding box again. See
+    /// [`Map::invalidate_region`](crate::Map::invalidate_region).
+    pub fn invalidate_region(&mut self, bounds: GeoBounds) {
+        self.tiles
+            .invalidate(|tile_id| tile_bounds(tile_id).intersects(&bounds));
+    }
 }
 
 impl Layer for TileLayer {
@@ -51,25 +145,34 @@ impl Layer for TileLayer {
 
     fn handle_input(&mut self, response: &Response, projection: &MapProjection) -> bool {
         self.visible_tiles = visible_tiles(projection).collect();
-        for (tile_id, _) in &self.visible_tiles {
+        for (tile_id, tile_rect) in &self.visible_tiles {
             load_tile(
                 &mut self.tiles,
                 self.config.as_ref(),
+                &self.loader,
                 &response.ctx,
                 *tile_id,
+                tile_priority(projection, *tile_rect),
             );
         }
+        self.tiles.evict();
+        show_tile_error(&self.tiles, response, projection);
         false
     }
 
-    fn draw(&self, painter: &Painter, _: &MapProjection) {
-        for (tile_id, tile_pos) in &self.visible_tiles {
+    fn draw(&self, painter: &Painter, projection: &MapProjection) {
+        let style = painter.ctx().global_style();
+        let rotation = ViewRotation::new(projection);
+        for (tile_id, tile_rect) in &self.visible_tiles {
             draw_tile(
                 &self.tiles,
                 painter,
                 tile_id,
-                *tile_pos,
+                *tile_rect,
+                &rotation,
                 self.tint.gamma_multiply(self.opacity),
+                self.placeholder.as_ref(),
+                &style.visuals,
             );
         }
     }
@@ -38,28 +38,79 @@
 //! }
 //! ```
 
+/// Caching of map tiles in memory and on disk.
+pub mod cache;
+
+/// Animated movement of the map view.
+pub mod camera;
+
 /// Configuration traits and types for the map widget.
 pub mod config;
 
+pub mod controls;
+
+/// Background downloading of map tiles.
+pub mod download;
+
+/// Events of the map widget.
+pub mod events;
+
+/// HTTP client settings for downloading map tiles.
+pub mod http;
+
+/// Keyboard navigation of the map.
+pub mod keyboard;
+
 /// Map layers.
 #[cfg(feature = "layers")]
 pub mod layers;
 
+/// Linking the views of several maps.
+pub mod link;
+
+/// Overview map in a corner of the map.
+pub mod minimap;
+
+pub mod offline;
+
+/// Drawing of tiles that are not loaded.
+pub mod placeholder;
+
 /// Map projection.
 pub mod projection;
 
+/// Tile sources other than tile servers.
+pub mod source;
+
+/// Saving and restoring the state of the map.
+pub mod state;
+
 use eframe::egui;
+use egui::emath::Rot2;
 use egui::{Color32, NumExt, Rect, Response, Sense, Ui, Vec2, Widget, pos2};
 use eyre::{Context, Result};
-use log::{debug, error};
-use poll_promise::Promise;
-use std::collections::{BTreeMap, HashMap};
+use log::{debug, error, warn};
+use reqwest::{StatusCode, header};
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
+use std::time::{Instant, SystemTime};
 use thiserror::Error;
 
+use crate::cache::{CacheMeta, DiskCache, MemoryCache, MemoryCacheStats, MemoryLimit};
+use crate::camera::{Animation, CameraAnimation, CameraMove, KineticPanning, Path, Target};
 use crate::config::MapConfig;
+use crate::controls::{ControlContext, MapControl};
+use crate::download::{Download, DownloadPool, RetryPolicy};
+use crate::events::{EventQueue, MapEvent};
+use crate::http::HttpClient;
+use crate::keyboard::KeyBindings;
 use crate::layers::Layer;
-use crate::projection::{GeoPos, MapProjection};
+use crate::link::{LinkOptions, LinkedMap, ViewLink};
+use crate::minimap::Minimap;
+use crate::placeholder::{Placeholder, PlaceholderFn, TileStatus};
+use crate::projection::{GeoBounds, GeoPos, MapProjection};
+use crate::source::TileSource;
+use crate::state::{LayerState, MapState};
 
 // The size of a map tile in pixels.
 const TILE_SIZE: u32 = 256;
@@ -67,18 +118,16 @@ const TILE_SIZE: u32 = 256;
 pub const MIN_ZOOM: u8 = 0;
 /// The maximum zoom level.
 pub const MAX_ZOOM: u8 = 19;
-
-// Reuse the reqwest client for all tile downloads by making it a static variable.
-static CLIENT: std::sync::LazyLock<reqwest::blocking::Client> = std::sync::LazyLock::new(|| {
-    reqwest::blocking::Client::builder()
-        .user_agent(format!(
-            "{}/{}",
-            env!("CARGO_PKG_NAME"),
-            env!("CARGO_PKG_VERSION")
-        ))
-        .build()
-        .expect("Failed to build reqwest client")
-});
+// How many points of scrolling zoom the map by one level.
+const SCROLL_POINTS_PER_ZOOM_LEVEL: f64 = 50.0;
+// The time constant of the zoom animation in seconds. The remaining zoom change decays by about
+// two thirds in this time.
+const ZOOM_ANIMATION_TIME: f64 = 0.08;
+// The height of the area at the bottom left corner of the map that the attribution is shown in.
+const ATTRIBUTION_HEIGHT: f32 = 30.0;
+// How many zoom levels up to look for a loaded tile to show in place of a loading one. At eight
+// levels up a single pixel of the ancestor covers the whole tile.
+const MAX_FALLBACK_DEPTH: u8 = 8;
 
 /// Errors that can occur while using the map widget.
 #[derive(Error, Debug)]
@@ -87,13 +136,47 @@ pub enum MapError {
     #[error("Connection error")]
     ConnectionError(#[from] reqwest::Error),
 
-    /// A map tile failed to download.
+    /// A map tile failed to download. Holds the HTTP status of the response.
     #[error("A map tile failed to download. HTTP Status: `{0}`")]
-    TileDownloadError(String),
+    TileDownloadError(StatusCode),
 
     /// The downloaded tile bytes could not be converted to an image.
     #[error("Unable to convert downloaded map tile bytes as image")]
     TileBytesConversionError(#[from] image::ImageError),
+
+    /// The map is offline and the tile is not in the disk cache.
+    #[error("The map tile is not available offline")]
+    TileNotAvailableOffline,
+
+    /// A map tile could not be read from a tile source.
+    #[error("Unable to read a map tile from the tile source: {0}")]
+    TileSourceError(String),
+
+    /// The settings of an HTTP client are invalid.
+    #[error("Unable to build the HTTP client: {0}")]
+    HttpClientError(String),
+}
+
+impl MapError {
+    /// Returns `true` if the error is likely temporary, such as a timeout or a server error, so
+    /// that loading the tile again later may succeed.
+    #[must_use]
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::ConnectionError(e) => {
+                e.is_timeout() || e.is_connect() || e.is_request() || e.is_body()
+            }
+            Self::TileDownloadError(status) => {
+                status.is_server_error()
+                    || *status == StatusCode::REQUEST_TIMEOUT
+                    || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            Self::TileBytesConversionError(_)
+            | Self::TileNotAvailableOffline
+            | Self::TileSourceError(_)
+            | Self::HttpClientError(_) => false,
+        }
+    }
 }
 
 /// A unique identifier for a map tile.
@@ -117,28 +200,128 @@ impl TileId {
 
 /// The state of a tile in the cache.
 enum Tile {
-    /// The tile is being downloaded.
-    Loading(Promise<Result<egui::ColorImage, Arc<eyre::Report>>>),
+    /// The tile is queued for download or being downloaded.
+    Loading {
+        download: Download,
+
+        /// The number of earlier attempts that failed.
+        attempts: u32,
+    },
 
     /// The tile is in memory.
     Loaded(egui::TextureHandle),
 
     /// The tile failed to download.
-    Failed(Arc<eyre::Report>),
+    Failed {
+        error: Arc<eyre::Report>,
+
+        /// The number of attempts that failed.
+        attempts: u32,
+
+        /// When the tile is loaded again, if the error was transient and retries are left.
+        retry_at: Option<Instant>,
+    },
 
     /// The tile state is unknown.
     Unknown,
 }
 
+/// Settings that control where the tiles of a map or a tile layer are loaded from.
+pub(crate) struct TileLoader {
+    /// Persistent cache consulted before downloading tiles.
+    disk_cache: Option<DiskCache>,
+
+    /// Worker threads that download the tiles.
+    download_pool: DownloadPool,
+
+    /// Whether tiles are only read from the disk cache.
+    offline: bool,
+
+    /// How tiles that failed with a transient error are retried.
+    retry_policy: RetryPolicy,
+
+    /// The HTTP client that downloads the tiles, overriding the client of the map configuration.
+    http_client: Option<HttpClient>,
+}
+
+impl Default for TileLoader {
+    fn default() -> Self {
+        Self {
+            disk_cache: None,
+            download_pool: DownloadPool::global(),
+            offline: false,
+            retry_policy: RetryPolicy::default(),
+            http_client: None,
+        }
+    }
+}
+
+/// A running zoom animation.
+#[derive(Clone, Copy, Debug)]
+struct ZoomAnimation {
+    /// The zoom level at the end of the animation.
+    target: f64,
+
+    /// The geographical position that stays in place while zooming.
+    anchor: GeoPos,
+
+    /// Where the anchor is on the screen, relative to the center of the widget.
+    anchor_offset: Vec2,
+}
+
 /// The map widget.
 pub struct Map {
     /// The geographical center of the map. (longitude, latitude)
     pub center: GeoPos,
 
-    /// The zoom level of the map.
-    pub zoom: u8,
+    /// The zoom level of the map. Between whole levels, the tiles of the nearest whole level are
+    /// shown scaled.
+    pub zoom: f64,
+
+    /// The direction the top of the map points to, in degrees clockwise from north. Zero is
+    /// north-up, and setting it to the heading of a vehicle gives a heading-up view.
+    pub bearing: f64,
+
+    /// Whether the map can be rotated with two fingers on a touch screen.
+    touch_rotation: bool,
 
-    tiles: HashMap<TileId, Tile>,
+    /// The zoom level the map is animating towards, after scrolling or pinching.
+    zoom_animation: Option<ZoomAnimation>,
+
+    /// A camera movement that starts the next time the map is shown.
+    pending_camera_move: Option<CameraMove>,
+
+    /// The running camera movement.
+    camera_animation: Option<CameraAnimation>,
+
+    /// How the map glides after a drag.
+    kinetic_panning: KineticPanning,
+
+    /// The speed of the glide in points per second.
+    pan_velocity: Vec2,
+
+    /// The keys that move the map while it has keyboard focus.
+    key_bindings: KeyBindings,
+
+    /// The area the view is kept inside of, if any.
+    max_bounds: Option<GeoBounds>,
+
+    /// How much space the widget takes.
+    size: MapSize,
+
+    /// The smallest size of the widget.
+    min_size: Vec2,
+
+    /// Events that the application has not taken yet.
+    events: EventQueue,
+
+    /// The center, zoom level and bearing last reported with [`MapEvent::ViewChanged`].
+    reported_view: (GeoPos, f64, f64),
+
+    /// Whether the pointer was over the map in the last frame.
+    hovered: bool,
+
+    tiles: MemoryCache,
 
     /// The geographical position under the mouse pointer, if any. (longitude, latitude)
     pub mouse_pos: Option<GeoPos>,
@@ -148,6 +331,39 @@ pub struct Map {
 
     /// Layers to be drawn on top of the base map.
     layers: BTreeMap<String, Box<dyn Layer>>,
+
+    /// The keys of the layers that are hidden.
+    hidden_layers: BTreeSet<String>,
+
+    /// The id the state of the map is persisted with in `egui::Memory`, if any.
+    persistence_id: Option<egui::Id>,
+
+    /// Whether the persisted state has been restored.
+    state_restored: bool,
+
+    /// The view the map follows, if it is linked to other maps.
+    view_link: Option<LinkedMap>,
+
+    /// The overview map in a corner of the map, if any.
+    minimap: Option<Minimap>,
+
+    /// The center of the minimap while it is dragged, which keeps it still under the pointer.
+    minimap_center: Option<GeoPos>,
+
+    /// The controls on top of the map, and the corners they are in.
+    controls: Vec<(egui::Align2, Box<dyn MapControl>)>,
+
+    /// Where the tiles are loaded from.
+    loader: TileLoader,
+
+    /// Draws the tiles that are loading or failed to load.
+    placeholder: Box<PlaceholderFn>,
+
+    /// Adds the entries of the application to the context menu, if any.
+    context_menu: Option<Box<ContextMenuFn>>,
+
+    /// Where the context menu was opened.
+    context_menu_pos: Option<GeoPos>,
 }
 
 impl Map {
@@ -162,12 +378,411 @@ impl Map {
         let max_zoom = config.max_zoom().max(min_zoom);
         let zoom = config.default_zoom().clamp(min_zoom, max_zoom);
         Self {
-            tiles: HashMap::new(),
+            bearing: 0.0,
+            touch_rotation: false,
+            zoom_animation: None,
+            pending_camera_move: None,
+            camera_animation: None,
+            kinetic_panning: KineticPanning::default(),
+            pan_velocity: Vec2::ZERO,
+            key_bindings: KeyBindings::default(),
+            max_bounds: None,
+            size: MapSize::default(),
+            min_size: Vec2::ZERO,
+            events: EventQueue::default(),
+            reported_view: (center, f64::from(zoom), 0.0),
+            hovered: false,
+            tiles: MemoryCache::default(),
             mouse_pos: None,
             config: Box::new(config),
             center,
-            zoom,
+            zoom: f64::from(zoom),
             layers: BTreeMap::new(),
+            hidden_layers: BTreeSet::new(),
+            persistence_id: None,
+            view_link: None,
+            minimap: None,
+            minimap_center: None,
+            controls: Vec::new(),
+            state_restored: false,
+            loader: TileLoader::default(),
+            placeholder: Box::new(placeholder::draw_default),
+            context_menu: None,
+            context_menu_pos: None,
+        }
+    }
+
+    /// Sets a persistent cache that is consulted before downloading tiles, and where downloaded
+    /// tiles are stored.
+    #[must_use]
+    pub fn with_disk_cache(mut self, disk_cache: DiskCache) -> Self {
+        self.loader.disk_cache = Some(disk_cache);
+        self
+    }
+
+    /// Sets the pool of worker threads that download the tiles. By default, all maps and tile
+    /// layers share [`DownloadPool::global`].
+    #[must_use]
+    pub fn with_download_pool(mut self, download_pool: DownloadPool) -> Self {
+        self.loader.download_pool = download_pool;
+        self
+    }
+
+    /// Sets the HTTP client that downloads the tiles, for example to send an access token or to
+    /// use a proxy. Overrides the client of the map configuration, see
+    /// [`MapConfig::http_client`].
+    #[must_use]
+    pub fn with_http_client(mut self, http_client: HttpClient) -> Self {
+        self.loader.http_client = Some(http_client);
+        self
+    }
+
+    /// Sets how tiles that are loading or failed to load are drawn. By default they are drawn
+    /// with [`placeholder::draw_default`], which picks its colours from the egui theme.
+    ///
+    /// The error of a failed tile is also shown in a tooltip when the tile is hovered.
+    #[must_use]
+    pub fn with_placeholder(
+        mut self,
+        placeholder: impl Fn(&egui::Painter, &Placeholder<'_>) + 'static,
+    ) -> Self {
+        self.placeholder = Box::new(placeholder);
+        self
+    }
+
+    /// Adds entries of the application to the context menu that opens with a secondary click.
+    /// `add_contents` gets the geographical position that was clicked. The entries of the layers,
+    /// such as deleting the node of an area, come before them, and are shown without any
+    /// application entries as well.
+    ///
+    /// ```
+    /// use egui_map_view::{Map, config::OpenStreetMapConfig};
+    ///
+    /// let map = Map::new(OpenStreetMapConfig::default()).with_context_menu(|ui, pos| {
+    ///     if ui.button("Copy coordinates").clicked() {
+    ///         ui.ctx().copy_text(format!("{:.5}, {:.5}", pos.lat, pos.lon));
+    ///     }
+    /// });
+    /// ```
+    #[must_use]
+    pub fn with_context_menu(
+        mut self,
+        add_contents: impl FnMut(&mut egui::Ui, GeoPos) + 'static,
+    ) -> Self {
+        self.context_menu = Some(Box::new(add_contents));
+        self
+    }
+
+    /// Links the view of the map to the other maps of `link`, so that they pan and zoom together.
+    /// See [`ViewLink`].
+    #[must_use]
+    pub fn with_view_link(mut self, link: &ViewLink, options: LinkOptions) -> Self {
+        self.view_link = Some(LinkedMap::new(link, options));
+        self
+    }
+
+    /// Shows an overview map in a corner of the map. See [`Minimap`].
+    #[must_use]
+    pub fn with_minimap(mut self, minimap: Minimap) -> Self {
+        self.minimap = Some(minimap);
+        self
+    }
+
+    /// Shows or hides the overview map. See [`Minimap`].
+    pub fn set_minimap(&mut self, minimap: Option<Minimap>) {
+        self.minimap = minimap;
+        self.minimap_center = None;
+    }
+
+    /// Returns the overview map, if the map shows one.
+    #[must_use]
+    pub fn minimap(&self) -> Option<&Minimap> {
+        self.minimap.as_ref()
+    }
+
+    /// Adds a control on top of the map, in the corner given by `anchor`. Controls in the same
+    /// corner are stacked in the order they are added. See [`controls`].
+    #[must_use]
+    pub fn with_control(
+        mut self,
+        anchor: egui::Align2,
+        control: impl MapControl + 'static,
+    ) -> Self {
+        self.add_control(anchor, control);
+        self
+    }
+
+    /// Adds a control on top of the map, in the corner given by `anchor`.
+    pub fn add_control(&mut self, anchor: egui::Align2, control: impl MapControl + 'static) {
+        self.controls.push((anchor, Box::new(control)));
+    }
+
+    /// Removes all the controls of the map.
+    pub fn clear_controls(&mut self) {
+        self.controls.clear();
+    }
+
+    /// Persists the state of the map in `egui::Memory` with `id`. See [`MapState`].
+    ///
+    /// The state is restored the first time the map is shown, and saved every frame after that.
+    /// eframe saves the memory between runs when its `persistence` feature is enabled. Add the
+    /// layers before showing the map, so that their opacity and visibility are restored too.
+    #[must_use]
+    pub fn with_persistence(mut self, id: egui::Id) -> Self {
+        self.persistence_id = Some(id);
+        self
+    }
+
+    /// Sets whether the map runs offline. An offline map reads its tiles only from the disk cache,
+    /// regardless of their age, and never contacts the tile server. Use
+    /// [`offline::seed`] to download a region into the disk cache beforehand.
+    #[must_use]
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.loader.offline = offline;
+        self
+    }
+
+    /// Switches the map between online and offline mode. See [`Map::with_offline`].
+    ///
+    /// Tiles that are already in memory are kept. Use [`Map::reload_tiles`] to load again the
+    /// tiles that were not available offline.
+    pub fn set_offline(&mut self, offline: bool) {
+        self.loader.offline = offline;
+    }
+
+    /// Returns `true` if the map reads its tiles only from the disk cache.
+    #[must_use]
+    pub fn is_offline(&self) -> bool {
+        self.loader.offline
+    }
+
+    /// Sets how many tiles are kept in memory. Tiles that are not visible are evicted, least
+    /// recently used first, when the limit is exceeded.
+    #[must_use]
+    pub fn with_memory_limit(mut self, limit: MemoryLimit) -> Self {
+        self.tiles.set_limit(limit);
+        self
+    }
+
+    /// Returns the usage statistics of the in-memory tile cache.
+    #[must_use]
+    pub fn memory_cache_stats(&self) -> MemoryCacheStats {
+        self.tiles.stats()
+    }
+
+    /// Drops all tiles from memory. Tiles are loaded again when they become visible.
+    pub fn clear_memory_cache(&mut self) {
+        self.tiles.clear();
+    }
+
+    /// Sets how tiles that failed to load because of a transient error, such as a timeout or a
+    /// server error, are retried.
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.loader.retry_policy = retry_policy;
+        self
+    }
+
+    /// Loads every tile again, including the ones that failed and are not retried anymore, for
+    /// example after the network connection has come back.
+    ///
+    /// Tiles that are fresh in the disk cache are read from there.
+    pub fn reload_tiles(&mut self) {
+        self.tiles.invalidate(|_| true);
+    }
+
+    /// Loads the tiles of every zoom level that overlap the bounding box again, for example after
+    /// the data of the region has changed.
+    ///
+    /// Tiles that are fresh in the disk cache are read from there.
+    pub fn invalidate_region(&mut self, bounds: GeoBounds) {
+        self.tiles
+            .invalidate(|tile_id| tile_bounds(tile_id).intersects(&bounds));
+    }
+
+    /// Sets whether the map can be rotated by turning two fingers on a touch screen. Off by
+    /// default.
+    #[must_use]
+    pub fn with_touch_rotation(mut self, touch_rotation: bool) -> Self {
+        self.touch_rotation = touch_rotation;
+        self
+    }
+
+    /// Sets how the map keeps gliding after it is released during a drag. Use
+    /// [`KineticPanning::disabled`] to stop the map as soon as it is released.
+    #[must_use]
+    pub fn with_kinetic_panning(mut self, kinetic_panning: KineticPanning) -> Self {
+        self.kinetic_panning = kinetic_panning;
+        self
+    }
+
+    /// Sets whether the map collects events for [`Map::take_events`]. Events are not collected by
+    /// default.
+    #[must_use]
+    pub fn with_events(mut self, events: bool) -> Self {
+        self.events.set_enabled(events);
+        self
+    }
+
+    /// Starts or stops collecting events. See [`Map::with_events`].
+    pub fn set_events(&mut self, events: bool) {
+        self.events.set_enabled(events);
+    }
+
+    /// Returns `true` if the map collects events.
+    #[must_use]
+    pub fn has_events(&self) -> bool {
+        self.events.is_enabled()
+    }
+
+    /// Takes the events that happened since the last call, oldest first. See [`MapEvent`].
+    ///
+    /// Events are collected while the map is shown if the map has [`Map::with_events`], so call
+    /// this every frame after showing the map.
+    pub fn take_events(&mut self) -> Vec<MapEvent> {
+        self.events.take()
+    }
+
+    /// Sets how much space the widget takes. See [`MapSize`].
+    #[must_use]
+    pub fn with_size(mut self, size: MapSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Makes the widget a fixed size in points.
+    #[must_use]
+    pub fn with_desired_size(self, size: Vec2) -> Self {
+        self.with_size(MapSize::Exact(size))
+    }
+
+    /// Makes the widget take all the available space, for example to fill a panel.
+    #[must_use]
+    pub fn with_fill_available(self) -> Self {
+        self.with_size(MapSize::FillAvailable)
+    }
+
+    /// Makes the widget as large as fits the available space with the given ratio of width to
+    /// height.
+    #[must_use]
+    pub fn with_aspect_ratio(self, ratio: f32) -> Self {
+        self.with_size(MapSize::AspectRatio(ratio))
+    }
+
+    /// Sets the smallest size of the widget in points.
+    #[must_use]
+    pub fn with_min_size(mut self, min_size: Vec2) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Sets how much space the widget takes. See [`MapSize`].
+    pub fn set_size(&mut self, size: MapSize) {
+        self.size = size;
+    }
+
+    /// Keeps the view inside a bounding box. The map cannot be panned outside the box, or zoomed
+    /// out further than where the box fills the widget.
+    ///
+    /// The box may cross the antimeridian, see [`GeoBounds::from_corners`].
+    #[must_use]
+    pub fn with_max_bounds(mut self, bounds: GeoBounds) -> Self {
+        self.max_bounds = Some(bounds);
+        self
+    }
+
+    /// Sets or removes the bounding box the view is kept inside of. See [`Map::with_max_bounds`].
+    pub fn set_max_bounds(&mut self, bounds: Option<GeoBounds>) {
+        self.max_bounds = bounds;
+    }
+
+    /// Returns the bounding box the view is kept inside of, if any.
+    #[must_use]
+    pub fn max_bounds(&self) -> Option<GeoBounds> {
+        self.max_bounds
+    }
+
+    /// Sets the keys that move the map while it has keyboard focus. Use
+    /// [`KeyBindings::disabled`] to turn keyboard navigation off.
+    #[must_use]
+    pub fn with_key_bindings(mut self, key_bindings: KeyBindings) -> Self {
+        self.key_bindings = key_bindings;
+        self
+    }
+
+    /// Moves the view to `center` and `zoom`, changing both gradually.
+    ///
+    /// The movement starts the next time the map is shown, and is stopped when the user pans or
+    /// zooms the map.
+    pub fn ease_to(&mut self, center: GeoPos, zoom: f64, animation: Animation) {
+        self.start_camera_move(Target::View { center, zoom }, Path::Ease, animation);
+    }
+
+    /// Moves the view to `center` and `zoom`, zooming out in the middle of the movement so that
+    /// the start and the end are briefly in view together. Suits long distances better than
+    /// [`Map::ease_to`].
+    pub fn fly_to(&mut self, center: GeoPos, zoom: f64, animation: Animation) {
+        self.start_camera_move(Target::View { center, zoom }, Path::Fly, animation);
+    }
+
+    /// Moves the view so that the bounding box fits in the widget, leaving `padding` points free
+    /// on every side. See [`MapProjection::fit_bounds`].
+    pub fn fit_bounds(&mut self, bounds: GeoBounds, padding: f32, animation: Animation) {
+        self.start_camera_move(Target::Bounds { bounds, padding }, Path::Ease, animation);
+    }
+
+    /// Returns `true` while the view is moving because of [`Map::ease_to`], [`Map::fly_to`] or
+    /// [`Map::fit_bounds`].
+    #[must_use]
+    pub fn is_animating(&self) -> bool {
+        self.pending_camera_move.is_some() || self.camera_animation.is_some()
+    }
+
+    /// Stops the camera movement where it is.
+    pub fn stop_animation(&mut self) {
+        self.pending_camera_move = None;
+        self.camera_animation = None;
+    }
+
+    fn start_camera_move(&mut self, target: Target, path: Path, animation: Animation) {
+        self.zoom_animation = None;
+        self.pan_velocity = Vec2::ZERO;
+        self.camera_animation = None;
+        self.pending_camera_move = Some(CameraMove {
+            target,
+            path,
+            animation,
+        });
+    }
+
+    /// Moves the view a step along the running camera movement.
+    fn animate_camera(&mut self, ui: &Ui, rect: &Rect) {
+        if let Some(camera_move) = self.pending_camera_move.take() {
+            let min_zoom = f64::from(self.config.min_zoom());
+            let max_zoom = f64::from(self.config.max_zoom()).max(min_zoom);
+            let min_zoom = self
+                .bounds_zoom(rect)
+                .map_or(min_zoom, |zoom| zoom.clamp(min_zoom, max_zoom));
+            self.camera_animation = Some(CameraAnimation::new(
+                camera_move,
+                self.center,
+                self.zoom,
+                *rect,
+                (min_zoom, max_zoom),
+            ));
+        }
+        let Some(animation) = &mut self.camera_animation else {
+            return;
+        };
+
+        let dt = f64::from(ui.input(|i| i.stable_dt));
+        let (center, zoom, finished) = animation.step(dt);
+        self.center = center;
+        self.zoom = zoom;
+        if finished {
+            self.camera_animation = None;
+        } else {
+            ui.ctx().request_repaint();
         }
     }
 
@@ -178,9 +793,64 @@ impl Map {
 
     /// Remove a layer from the map
     pub fn remove_layer(&mut self, key: &str) -> bool {
+        self.hidden_layers.remove(key);
         self.layers.remove(key).is_some()
     }
 
+    /// Shows or hides a layer. A hidden layer is not drawn and does not handle input.
+    pub fn set_layer_visible(&mut self, key: &str, visible: bool) {
+        if visible {
+            self.hidden_layers.remove(key);
+        } else if self.layers.contains_key(key) {
+            self.hidden_layers.insert(key.to_owned());
+        }
+    }
+
+    /// Returns `true` if the layer is shown, or `false` if it is hidden or there is no such layer.
+    #[must_use]
+    pub fn is_layer_visible(&self, key: &str) -> bool {
+        self.layers.contains_key(key) && !self.hidden_layers.contains(key)
+    }
+
+    /// Returns the current view of the map and the opacity and visibility of its layers.
+    #[must_use]
+    pub fn state(&self) -> MapState {
+        MapState {
+            center: self.center,
+            zoom: self.zoom,
+            bearing: self.bearing,
+            layers: self
+                .layers
+                .iter()
+                .map(|(key, layer)| {
+                    let state = LayerState {
+                        opacity: layer.opacity(),
+                        visible: !self.hidden_layers.contains(key),
+                    };
+                    (key.clone(), state)
+                })
+                .collect(),
+        }
+    }
+
+    /// Restores a state taken with [`Map::state`]. Layers that are not in the map are ignored,
+    /// and the zoom level is kept within the limits of the map configuration.
+    pub fn set_state(&mut self, state: &MapState) {
+        let min_zoom = f64::from(self.config.min_zoom());
+        let max_zoom = f64::from(self.config.max_zoom()).max(min_zoom);
+        self.center = state.center;
+        self.zoom = state.zoom.clamp(min_zoom, max_zoom);
+        self.bearing = state.bearing;
+        self.stop_motion();
+
+        for (key, layer_state) in &state.layers {
+            if let Some(layer) = self.layers.get_mut(key) {
+                layer.set_opacity(layer_state.opacity);
+                self.set_layer_visible(key, layer_state.visible);
+            }
+        }
+    }
+
     /// Get a reference to the layers.
     #[must_use]
     pub fn layers(&self) -> &BTreeMap<String, Box<dyn Layer>> {
@@ -209,136 +879,607 @@ impl Map {
 
     /// Handles user input for panning and zooming.
     fn handle_input(&mut self, ui: &Ui, rect: &Rect, response: &Response) {
-        // Handle panning
-        if response.dragged() {
-            let delta = response.drag_delta();
-            let center_in_tiles_x = lon_to_x(self.center.lon, self.zoom);
-            let center_in_tiles_y = lat_to_y(self.center.lat, self.zoom);
-
-            let mut new_center_x = center_in_tiles_x - (f64::from(delta.x) / f64::from(TILE_SIZE));
-            let mut new_center_y = center_in_tiles_y - (f64::from(delta.y) / f64::from(TILE_SIZE));
-
-            // Clamp the new center to the map boundaries.
-            let world_size_in_tiles = 2.0_f64.powi(i32::from(self.zoom));
-            let view_size_in_tiles_x = f64::from(rect.width()) / f64::from(TILE_SIZE);
-            let view_size_in_tiles_y = f64::from(rect.height()) / f64::from(TILE_SIZE);
-
-            let min_center_x = view_size_in_tiles_x / 2.0;
-            let max_center_x = world_size_in_tiles - view_size_in_tiles_x / 2.0;
-            let min_center_y = view_size_in_tiles_y / 2.0;
-            let max_center_y = world_size_in_tiles - view_size_in_tiles_y / 2.0;
-
-            // If the map is smaller than the viewport, center it. Otherwise, clamp the center.
-            new_center_x = if min_center_x > max_center_x {
-                world_size_in_tiles / 2.0
-            } else {
-                new_center_x.clamp(min_center_x, max_center_x)
-            };
-            new_center_y = if min_center_y > max_center_y {
-                world_size_in_tiles / 2.0
+        // Take keyboard focus when the map is used with the pointer.
+        if response.clicked() || response.drag_started() {
+            response.request_focus();
+        }
+        if response.has_focus() {
+            self.handle_keyboard(ui, rect, response);
+        }
+
+        // A press stops the glide of an earlier drag.
+        if response.is_pointer_button_down_on() {
+            self.pan_velocity = Vec2::ZERO;
+        }
+
+        // Handle pinch-to-zoom and two-finger panning on touch screens. The pointer follows only
+        // one of the fingers, so it is not used for panning during the gesture.
+        let multi_touch = ui
+            .input(|i| i.multi_touch())
+            .filter(|touch| rect.contains(touch.center_pos));
+        if let Some(touch) = multi_touch {
+            self.stop_animation();
+            self.pan_velocity = Vec2::ZERO;
+            self.zoom = self.zoom_target();
+            self.zoom_animation = None;
+            let zoom = self.zoom + f64::from(touch.zoom_delta).log2();
+            let bearing = if self.touch_rotation {
+                // The map turns with the fingers.
+                (self.bearing - f64::from(touch.rotation_delta).to_degrees()).rem_euclid(360.0)
             } else {
-                new_center_y.clamp(min_center_y, max_center_y)
+                self.bearing
             };
+            let from = touch.center_pos - touch.translation_delta;
+            self.pinch(zoom, bearing, from, touch.center_pos, rect);
+            ui.ctx().request_repaint();
+            return;
+        }
 
-            self.center = (
-                x_to_lon(new_center_x, self.zoom),
-                y_to_lat(new_center_y, self.zoom),
-            )
-                .into();
+        // Handle panning
+        if response.dragged() {
+            self.stop_animation();
+            self.pan_by(response.drag_delta(), rect);
+        }
+
+        // Keep gliding after the map is released.
+        if response.drag_stopped() {
+            let velocity = ui.input(|i| i.pointer.velocity());
+            self.pan_velocity = velocity * self.kinetic_panning.sensitivity;
         }
 
         // Handle double-click to zoom and center
         if response.double_clicked()
             && let Some(pointer_pos) = response.interact_pointer_pos()
         {
-            let min_zoom = self.config.min_zoom();
-            let max_zoom = self.config.max_zoom().max(min_zoom);
-            let new_zoom = (self.zoom + 1).clamp(min_zoom, max_zoom);
-
-            if new_zoom != self.zoom {
-                // Determine the geo-coordinate under the mouse cursor before the zoom
-                let mouse_rel = pointer_pos - rect.min;
-                let center_x = lon_to_x(self.center.lon, self.zoom);
-                let center_y = lat_to_y(self.center.lat, self.zoom);
-                let widget_center_x = f64::from(rect.width()) / 2.0;
-                let widget_center_y = f64::from(rect.height()) / 2.0;
-
-                let target_x =
-                    center_x + (f64::from(mouse_rel.x) - widget_center_x) / f64::from(TILE_SIZE);
-                let target_y =
-                    center_y + (f64::from(mouse_rel.y) - widget_center_y) / f64::from(TILE_SIZE);
-
-                let new_center_lon = x_to_lon(target_x, self.zoom);
-                let new_center_lat = y_to_lat(target_y, self.zoom);
-
-                // Set the new zoom level and center the map on the clicked location
-                self.zoom = new_zoom;
-                self.center = (new_center_lon, new_center_lat).into();
-            }
+            // Center the map on the clicked location and zoom in to the next whole level.
+            self.center = self.projection(*rect).unproject(pointer_pos);
+            let target = self.zoom_target().floor() + 1.0;
+            self.zoom_towards(target, rect.center(), rect);
         }
 
-        // Handle scroll-to-zoom
+        // Handle scroll-to-zoom and trackpad pinch-to-zoom
         if response.hovered()
             && let Some(mouse_pos) = response.hover_pos()
         {
-            let mouse_rel = mouse_pos - rect.min;
-
-            // Determine the geo-coordinate under the mouse cursor.
-            let center_x = lon_to_x(self.center.lon, self.zoom);
-            let center_y = lat_to_y(self.center.lat, self.zoom);
-            let widget_center_x = f64::from(rect.width()) / 2.0;
-            let widget_center_y = f64::from(rect.height()) / 2.0;
-
-            let target_x =
-                center_x + (f64::from(mouse_rel.x) - widget_center_x) / f64::from(TILE_SIZE);
-            let target_y =
-                center_y + (f64::from(mouse_rel.y) - widget_center_y) / f64::from(TILE_SIZE);
-
-            let scroll = ui.input(|i| i.smooth_scroll_delta.y);
-            if scroll != 0.0 {
-                let min_zoom = self.config.min_zoom();
-                let max_zoom = self.config.max_zoom().max(min_zoom);
-                let old_zoom = self.zoom;
-                let mut new_zoom = (i32::from(self.zoom) + scroll.signum() as i32)
-                    .clamp(i32::from(min_zoom), i32::from(max_zoom))
-                    as u8;
-
-                // If we are zooming out, check if the new zoom level is valid.
-                if scroll < 0.0 {
-                    let world_pixel_size = 2.0_f64.powi(i32::from(new_zoom)) * f64::from(TILE_SIZE);
-                    // If the world size would become smaller than the widget size, reject the zoom.
-                    if world_pixel_size < f64::from(rect.width())
-                        || world_pixel_size < f64::from(rect.height())
-                    {
-                        new_zoom = old_zoom; // Effectively cancel the zoom by reverting to the old value.
-                    }
-                }
+            let (scroll, zoom_delta) = ui.input(|i| (i.smooth_scroll_delta.y, i.zoom_delta()));
+            let levels =
+                f64::from(scroll) / SCROLL_POINTS_PER_ZOOM_LEVEL + f64::from(zoom_delta).log2();
+            if levels != 0.0 {
+                self.zoom_towards(self.zoom_target() + levels, mouse_pos, rect);
+            }
+        }
+    }
 
-                if new_zoom != old_zoom {
-                    let target_lon = x_to_lon(target_x, old_zoom);
-                    let target_lat = y_to_lat(target_y, old_zoom);
+    /// Returns the projection of the current view into the widget.
+    fn projection(&self, rect: Rect) -> MapProjection {
+        MapProjection::new(self.zoom, self.center, rect).with_bearing(self.bearing)
+    }
 
-                    // Set the new zoom level
-                    self.zoom = new_zoom;
+    /// Adds the events of the pointer on the map. Clicks and drags that a layer handled are left
+    /// out.
+    fn collect_pointer_events(
+        &mut self,
+        response: &Response,
+        projection: &MapProjection,
+        input_handled_by_layer: bool,
+    ) {
+        let hover_pos = response.hover_pos().map(|pos| projection.unproject(pos));
+        match (self.hovered, hover_pos) {
+            (false, Some(pos)) => self.events.push(MapEvent::HoverEnter { pos }),
+            (true, None) => self.events.push(MapEvent::HoverLeave),
+            _ => {}
+        }
+        self.hovered = hover_pos.is_some();
 
-                    // Adjust the map center so the geo-coordinate under the mouse remains the
-                    // same
-                    let new_target_x = lon_to_x(target_lon, new_zoom);
-                    let new_target_y = lat_to_y(target_lat, new_zoom);
+        let Some(pos) = response
+            .interact_pointer_pos()
+            .map(|pos| projection.unproject(pos))
+        else {
+            return;
+        };
+        if input_handled_by_layer {
+            return;
+        }
 
-                    let new_center_x = new_target_x
-                        - (f64::from(mouse_rel.x) - widget_center_x) / f64::from(TILE_SIZE);
-                    let new_center_y = new_target_y
-                        - (f64::from(mouse_rel.y) - widget_center_y) / f64::from(TILE_SIZE);
+        for button in [
+            egui::PointerButton::Primary,
+            egui::PointerButton::Secondary,
+            egui::PointerButton::Middle,
+        ] {
+            if response.clicked_by(button) {
+                self.events.push(MapEvent::Clicked { pos, button });
+            }
+        }
+        if response.double_clicked() {
+            self.events.push(MapEvent::DoubleClicked { pos });
+        }
+        if response.long_touched() {
+            self.events.push(MapEvent::LongPressed { pos });
+        }
+        if response.drag_started() {
+            self.events.push(MapEvent::DragStarted { pos });
+        }
+        if response.drag_stopped() {
+            self.events.push(MapEvent::DragEnded { pos });
+        }
+    }
 
-                    self.center = (
-                        x_to_lon(new_center_x, new_zoom),
-                        y_to_lat(new_center_y, new_zoom),
-                    )
-                        .into();
+    /// Shows the context menu, if the map has one, with the entries of the layers first.
+    fn show_context_menu(&mut self, response: &Response, projection: &MapProjection) {
+        let hidden_layers = &self.hidden_layers;
+        if self.context_menu.is_none() && self.layers.keys().all(|key| hidden_layers.contains(key))
+        {
+            return;
+        }
+        if response.secondary_clicked() {
+            self.context_menu_pos = response
+                .interact_pointer_pos()
+                .map(|pos| projection.unproject(pos));
+        }
+        let Some(pos) = self.context_menu_pos else {
+            return;
+        };
+        let layers = &mut self.layers;
+        let add_contents = &mut self.context_menu;
+        response.context_menu(|ui| {
+            for (key, layer) in layers.iter_mut() {
+                if !hidden_layers.contains(key) {
+                    layer.context_menu(ui, pos, projection);
                 }
             }
+            if let Some(add_contents) = add_contents {
+                add_contents(ui, pos);
+            }
+            // The layers may have no entries for the clicked position.
+            if ui.min_rect().area() <= 0.0 {
+                ui.close();
+            }
+        });
+    }
+
+    /// Returns where the minimap is and its projection, if the map shows one.
+    fn minimap_projection(&self, rect: Rect) -> Option<(Rect, MapProjection)> {
+        let minimap = self.minimap?;
+        let minimap_rect = minimap.rect(rect);
+        let projection = minimap.projection(
+            minimap_rect,
+            self.minimap_center.unwrap_or(self.center),
+            self.zoom,
+            f64::from(self.config.min_zoom()),
+        );
+        Some((minimap_rect, projection))
+    }
+
+    /// Pans the map when the minimap is dragged, and moves it to the point that is clicked on
+    /// the minimap.
+    fn handle_minimap_input(&mut self, ui: &Ui, rect: Rect, id: egui::Id) {
+        let Some((minimap_rect, projection)) = self.minimap_projection(rect) else {
+            return;
+        };
+        let response = ui.interact(
+            minimap_rect,
+            id.with("minimap"),
+            Sense::drag().union(Sense::click()),
+        );
+
+        if response.drag_started() {
+            // Keep the minimap still, so that the outline of the view follows the pointer.
+            self.minimap_center = Some(self.center);
+        }
+        if response.dragged() {
+            let center = projection.project(self.center) + response.drag_delta();
+            self.center = projection.unproject(center);
+            self.stop_motion();
+            ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
+        } else if response.hovered() {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+        }
+        if response.drag_stopped() {
+            self.minimap_center = None;
+        }
+
+        if response.clicked()
+            && let Some(pos) = response.interact_pointer_pos()
+        {
+            let zoom = self.zoom_target();
+            self.ease_to(projection.unproject(pos), zoom, Animation::default());
+        }
+    }
+
+    /// Draws the minimap with the outline of the view of the map, which covers `rect`.
+    fn draw_minimap(&mut self, ui: &Ui, rect: Rect, map_projection: &MapProjection) {
+        let Some((minimap_rect, projection)) = self.minimap_projection(rect) else {
+            return;
+        };
+        let visuals = ui.visuals();
+        let painter = ui.painter_at(minimap_rect);
+        painter.rect_filled(minimap_rect, 0.0, visuals.extreme_bg_color);
+        // Load the tiles of the minimap after the tiles of the map, which are at most a tile
+        // further than the corners of the map from its center.
+        let priority_offset = rect.size().length() + 2.0 * TILE_SIZE as f32;
+        draw_map(
+            &mut self.tiles,
+            self.config.as_ref(),
+            &self.loader,
+            &painter,
+            &projection,
+            self.placeholder.as_ref(),
+            visuals,
+            priority_offset,
+        );
+
+        let outline = minimap::view_outline(map_projection, rect, &projection);
+        let stroke = visuals.selection.stroke;
+        painter.add(egui::Shape::convex_polygon(
+            outline,
+            stroke.color.gamma_multiply(0.2),
+            stroke,
+        ));
+        painter.rect_stroke(
+            minimap_rect,
+            0.0,
+            visuals.widgets.noninteractive.bg_stroke,
+            egui::StrokeKind::Inside,
+        );
+    }
+
+    /// Shows the controls in their corners of the map.
+    fn show_controls(&mut self, ui: &mut Ui, rect: Rect) {
+        if self.controls.is_empty() {
+            return;
+        }
+        let before = self.state();
+
+        // The controls get the map to change, so they are taken out of it while they are shown.
+        let mut controls = std::mem::take(&mut self.controls);
+        let mut anchors: Vec<egui::Align2> = Vec::new();
+        for (anchor, _) in &controls {
+            if !anchors.contains(anchor) {
+                anchors.push(*anchor);
+            }
+        }
+        let frame = egui::Frame::NONE
+            .inner_margin(egui::Margin::same(3))
+            .fill(ui.visuals().window_fill)
+            .stroke(ui.visuals().window_stroke)
+            .corner_radius(3.0);
+        for (index, anchor) in anchors.into_iter().enumerate() {
+            let layout = if anchor.y() == egui::Align::Max {
+                egui::Layout::bottom_up(anchor.x())
+            } else {
+                egui::Layout::top_down(anchor.x())
+            };
+            let mut child_ui = ui.new_child(
+                egui::UiBuilder::new()
+                    .max_rect(self.controls_rect(rect, anchor))
+                    .layout(layout)
+                    .id_salt(("controls", index)),
+            );
+            for (_, control) in controls.iter_mut().filter(|(a, _)| *a == anchor) {
+                frame.show(&mut child_ui, |ui| {
+                    control.ui(ui, &mut ControlContext::new(self, rect));
+                });
+            }
+        }
+        // Keep the controls that were added while the controls were shown.
+        controls.append(&mut self.controls);
+        self.controls = controls;
+
+        // The map is shown again right away to show and animate the changes.
+        if self.state() != before
+            || self.zoom_animation.is_some()
+            || self.pending_camera_move.is_some()
+        {
+            ui.ctx().request_repaint();
+        }
+    }
+
+    /// Returns the part of the map that the controls in the corner given by `anchor` are placed
+    /// in, which leaves out the attribution and the minimap.
+    fn controls_rect(&self, rect: Rect, anchor: egui::Align2) -> Rect {
+        const MARGIN: f32 = 10.0;
+        let mut area = rect.shrink(MARGIN);
+        if let Some(minimap) = &self.minimap
+            && minimap.anchor == anchor
+        {
+            let minimap_rect = minimap.rect(rect);
+            if anchor.y() == egui::Align::Max {
+                area.max.y = minimap_rect.min.y - MARGIN;
+            } else {
+                area.min.y = minimap_rect.max.y + MARGIN;
+            }
+        }
+        if anchor == egui::Align2::LEFT_BOTTOM && self.config.attribution().is_some() {
+            area.max.y -= ATTRIBUTION_HEIGHT;
+        }
+        area
+    }
+
+    /// Stops the animations and the glide of the map.
+    fn stop_motion(&mut self) {
+        self.zoom_animation = None;
+        self.pending_camera_move = None;
+        self.camera_animation = None;
+        self.pan_velocity = Vec2::ZERO;
+    }
+
+    /// Moves the map to the linked view, if another map has changed it.
+    fn follow_link(&mut self, rect: &Rect) {
+        let Some((center, zoom)) = self
+            .view_link
+            .as_mut()
+            .and_then(|link| link.pull(self.center, self.zoom))
+        else {
+            return;
+        };
+        let min_zoom = f64::from(self.config.min_zoom());
+        let max_zoom = f64::from(self.config.max_zoom()).max(min_zoom);
+        self.center = center;
+        self.zoom = zoom.clamp(min_zoom, max_zoom);
+        self.stop_motion();
+        self.constrain_to_bounds(rect);
+
+        // Keep the clamped view from being pushed back to the other maps.
+        if let Some(link) = &mut self.view_link {
+            link.mark_synced(self.center, self.zoom);
+        }
+    }
+
+    /// Handles the keys of the key bindings while the map has keyboard focus.
+    fn handle_keyboard(&mut self, ui: &Ui, rect: &Rect, response: &Response) {
+        // Keep the arrow keys from moving the focus to other widgets.
+        ui.memory_mut(|memory| {
+            memory.set_focus_lock_filter(
+                response.id,
+                egui::EventFilter {
+                    horizontal_arrows: true,
+                    vertical_arrows: true,
+                    ..Default::default()
+                },
+            );
+        });
+
+        let (direction, zoom_steps, reset, dt) = ui.input(|i| {
+            (
+                self.key_bindings.pan_direction(i),
+                self.key_bindings.zoom_steps(i),
+                self.key_bindings.reset_pressed(i),
+                i.stable_dt,
+            )
+        });
+
+        if direction != Vec2::ZERO {
+            self.stop_animation();
+            self.pan_velocity = Vec2::ZERO;
+            // Moving the view one way moves the map the other way.
+            self.pan_by(-direction * self.key_bindings.pan_speed * dt, rect);
+            ui.ctx().request_repaint();
+        }
+
+        if zoom_steps != 0 {
+            let target = self.zoom_target().round() + f64::from(zoom_steps);
+            self.zoom_towards(target, rect.center(), rect);
+        }
+
+        if reset {
+            let center = self.config.default_center().into();
+            let zoom = f64::from(self.config.default_zoom());
+            self.ease_to(center, zoom, Animation::default());
+        }
+    }
+
+    /// Moves the map by `delta` points on the screen, keeping the world inside the widget.
+    fn pan_by(&mut self, delta: Vec2, rect: &Rect) {
+        let projection = self.projection(*rect);
+        let north_up_delta = projection.rotation().inverse() * delta;
+        let center_in_tiles_x = lon_to_x(self.center.lon, self.zoom);
+        let center_in_tiles_y = lat_to_y(self.center.lat, self.zoom);
+
+        let new_center_x = center_in_tiles_x - (f64::from(north_up_delta.x) / f64::from(TILE_SIZE));
+        let mut new_center_y =
+            center_in_tiles_y - (f64::from(north_up_delta.y) / f64::from(TILE_SIZE));
+
+        // Clamp the new center to the top and bottom of the world. The world repeats horizontally,
+        // so the map can be panned across the antimeridian.
+        let world_size_in_tiles = 2.0_f64.powf(self.zoom);
+        let view_size = projection.view_size();
+        let view_size_in_tiles_y = f64::from(view_size.y) / f64::from(TILE_SIZE);
+
+        let min_center_y = view_size_in_tiles_y / 2.0;
+        let max_center_y = world_size_in_tiles - view_size_in_tiles_y / 2.0;
+
+        // If the map is smaller than the viewport, center it. Otherwise, clamp the center.
+        new_center_y = if min_center_y > max_center_y {
+            world_size_in_tiles / 2.0
+        } else {
+            new_center_y.clamp(min_center_y, max_center_y)
+        };
+
+        self.center = (
+            wrap_lon(x_to_lon(new_center_x, self.zoom)),
+            y_to_lat(new_center_y, self.zoom),
+        )
+            .into();
+
+        // The point the map zooms around moves with the map.
+        if let Some(animation) = &mut self.zoom_animation {
+            animation.anchor_offset += delta;
+        }
+    }
+
+    /// Zooms to `zoom` and turns to `bearing` at once, and moves the geographical position under
+    /// `from` to `to`, as the fingers of a pinch gesture do.
+    fn pinch(&mut self, zoom: f64, bearing: f64, from: egui::Pos2, to: egui::Pos2, rect: &Rect) {
+        let geo_pos = self.projection(*rect).unproject(from);
+        self.bearing = bearing;
+        let (lowest, highest) = self.zoom_limits(rect, self.zoom);
+        self.zoom = zoom.clamp(lowest, highest);
+        self.place(geo_pos, to - rect.center());
+
+        // Keep the world inside the widget.
+        self.pan_by(Vec2::ZERO, rect);
+    }
+
+    /// Moves the center so that `geo_pos` is `offset` points from the center of the widget on
+    /// the screen.
+    fn place(&mut self, geo_pos: GeoPos, offset: Vec2) {
+        let rotation = Rot2::from_angle(-self.bearing.to_radians() as f32);
+        let offset = rotation.inverse() * offset;
+        let x = lon_to_x(geo_pos.lon, self.zoom);
+        let y = lat_to_y(geo_pos.lat, self.zoom);
+        let center_x = x - f64::from(offset.x) / f64::from(TILE_SIZE);
+        let center_y = y - f64::from(offset.y) / f64::from(TILE_SIZE);
+        self.center = (
+            wrap_lon(x_to_lon(center_x, self.zoom)),
+            y_to_lat(center_y, self.zoom),
+        )
+            .into();
+    }
+
+    /// Moves the map a step along the glide that follows a drag.
+    fn glide(&mut self, ui: &Ui, rect: &Rect) {
+        if self.pan_velocity == Vec2::ZERO {
+            return;
+        }
+
+        let dt = ui.input(|i| i.stable_dt);
+        let (offset, velocity) = self.kinetic_panning.step(self.pan_velocity, dt);
+        self.pan_by(offset, rect);
+        self.pan_velocity = velocity;
+        if velocity != Vec2::ZERO {
+            ui.ctx().request_repaint();
+        }
+    }
+
+    /// Returns the zoom level the map is animating towards, or the current zoom level.
+    fn zoom_target(&self) -> f64 {
+        self.zoom_animation
+            .map_or(self.zoom, |animation| animation.target)
+    }
+
+    /// Starts animating the zoom level towards `target`, keeping the geographical position under
+    /// `anchor` in place.
+    ///
+    /// The target is limited to the zoom levels of the map configuration. The map is not zoomed
+    /// out further once the world has become shorter than the widget.
+    fn zoom_towards(&mut self, target: f64, anchor: egui::Pos2, rect: &Rect) {
+        self.stop_animation();
+        let current = self.zoom_target();
+        let (lowest, highest) = self.zoom_limits(rect, current);
+        let target = target.clamp(lowest, highest);
+        if target == current {
+            return;
+        }
+
+        let projection = self.projection(*rect);
+        self.zoom_animation = Some(ZoomAnimation {
+            target,
+            anchor: projection.unproject(anchor),
+            anchor_offset: anchor - rect.center(),
+        });
+    }
+
+    /// Returns the lowest and highest zoom level the user can zoom to from `current`.
+    ///
+    /// These are the zoom levels of the map configuration, except that the map is not zoomed out
+    /// further once the world has become shorter than the widget.
+    fn zoom_limits(&self, rect: &Rect, current: f64) -> (f64, f64) {
+        let min_zoom = f64::from(self.config.min_zoom());
+        let max_zoom = f64::from(self.config.max_zoom()).max(min_zoom);
+        let view_size = self.projection(*rect).view_size();
+        let fit_zoom = (f64::from(view_size.y) / f64::from(TILE_SIZE)).log2();
+        let mut lowest = min_zoom.max(fit_zoom).min(current);
+        if let Some(bounds_zoom) = self.bounds_zoom(rect) {
+            lowest = lowest.max(bounds_zoom);
+        }
+        (lowest.min(max_zoom), max_zoom)
+    }
+
+    /// Returns the lowest zoom level at which the max bounds fill the widget, if the map has
+    /// max bounds.
+    fn bounds_zoom(&self, rect: &Rect) -> Option<f64> {
+        let bounds = self.max_bounds?;
+        let (min_lon, max_lon) = bounds.lon_range();
+        let width = lon_to_x(max_lon, 0.0) - lon_to_x(min_lon, 0.0);
+        let height = lat_to_y(bounds.min.lat, 0.0) - lat_to_y(bounds.max.lat, 0.0);
+        let view_size = self.projection(*rect).view_size();
+        let zoom_x = (f64::from(view_size.x) / (width * f64::from(TILE_SIZE))).log2();
+        let zoom_y = (f64::from(view_size.y) / (height * f64::from(TILE_SIZE))).log2();
+        Some(zoom_x.max(zoom_y))
+    }
+
+    /// Keeps the view inside the max bounds, if the map has them.
+    fn constrain_to_bounds(&mut self, rect: &Rect) {
+        let (Some(bounds), Some(bounds_zoom)) = (self.max_bounds, self.bounds_zoom(rect)) else {
+            return;
+        };
+
+        let max_zoom = f64::from(self.config.max_zoom());
+        let lowest = bounds_zoom.min(max_zoom);
+        self.zoom = self.zoom.max(lowest);
+        if let Some(animation) = &mut self.zoom_animation {
+            animation.target = animation.target.max(lowest);
+        }
+
+        // Keep the edges of the view inside the bounds, or center the view on the bounds if it
+        // is larger than them.
+        let clamp = |value: f64, min: f64, max: f64| {
+            if min > max {
+                (min + max) / 2.0
+            } else {
+                value.clamp(min, max)
+            }
+        };
+        let view_size = self.projection(*rect).view_size();
+        let half_view_x = f64::from(view_size.x) / 2.0 / f64::from(TILE_SIZE);
+        let half_view_y = f64::from(view_size.y) / 2.0 / f64::from(TILE_SIZE);
+        // Use the copy of the center longitude nearest to the middle of the bounds.
+        let (min_lon, max_lon) = bounds.lon_range();
+        let middle_lon = (min_lon + max_lon) / 2.0;
+        let x = lon_to_x(
+            middle_lon + wrap_lon(self.center.lon - middle_lon),
+            self.zoom,
+        );
+        let y = lat_to_y(self.center.lat, self.zoom);
+        let center_x = clamp(
+            x,
+            lon_to_x(min_lon, self.zoom) + half_view_x,
+            lon_to_x(max_lon, self.zoom) - half_view_x,
+        );
+        let center_y = clamp(
+            y,
+            lat_to_y(bounds.max.lat, self.zoom) + half_view_y,
+            lat_to_y(bounds.min.lat, self.zoom) - half_view_y,
+        );
+        if center_x != x || center_y != y {
+            // Kinetic panning stops at the edge instead of pushing against it.
+            self.pan_velocity = Vec2::ZERO;
+            self.center = (
+                wrap_lon(x_to_lon(center_x, self.zoom)),
+                y_to_lat(center_y, self.zoom),
+            )
+                .into();
+        }
+    }
+
+    /// Moves the zoom level a step closer to the target of the running zoom animation.
+    fn animate_zoom(&mut self, ui: &Ui) {
+        let Some(animation) = self.zoom_animation else {
+            return;
+        };
+
+        let dt = f64::from(ui.input(|i| i.stable_dt));
+        let step = 1.0 - (-dt / ZOOM_ANIMATION_TIME).exp();
+        self.zoom += (animation.target - self.zoom) * step;
+        if (animation.target - self.zoom).abs() < 1e-3 {
+            self.zoom = animation.target;
+            self.zoom_animation = None;
+        } else {
+            ui.ctx().request_repaint();
         }
+
+        // Keep the anchor at the same place on the screen.
+        self.place(animation.anchor, animation.anchor_offset);
     }
 
     /// Draws the attribution text.
@@ -366,8 +1507,8 @@ impl Map {
             let mut child_ui = ui.new_child(
                 egui::UiBuilder::new()
                     .max_rect(Rect::from_min_size(
-                        attribution_pos - egui::vec2(0.0, 30.0),
-                        egui::vec2(rect.width() - 10.0, 30.0),
+                        attribution_pos - egui::vec2(0.0, ATTRIBUTION_HEIGHT),
+                        egui::vec2(rect.width() - 10.0, ATTRIBUTION_HEIGHT),
                     ))
                     .id_salt("attribution"),
             );
@@ -389,249 +1530,717 @@ impl Map {
 }
 
 /// Converts longitude to the x-coordinate of a tile at a given zoom level.
-fn lon_to_x(lon: f64, zoom: u8) -> f64 {
-    (lon + 180.0) / 360.0 * (2.0_f64.powi(i32::from(zoom)))
+fn lon_to_x(lon: f64, zoom: f64) -> f64 {
+    (lon + 180.0) / 360.0 * (2.0_f64.powf(zoom))
 }
 
 /// Converts latitude to the y-coordinate of a tile at a given zoom level.
-fn lat_to_y(lat: f64, zoom: u8) -> f64 {
-    (1.0 - lat.to_radians().tan().asinh() / std::f64::consts::PI) / 2.0
-        * (2.0_f64.powi(i32::from(zoom)))
+fn lat_to_y(lat: f64, zoom: f64) -> f64 {
+    (1.0 - lat.to_radians().tan().asinh() / std::f64::consts::PI) / 2.0 * (2.0_f64.powf(zoom))
 }
 
 /// Converts the x-coordinate of a tile to longitude at a given zoom level.
-fn x_to_lon(x: f64, zoom: u8) -> f64 {
-    x / (2.0_f64.powi(i32::from(zoom))) * 360.0 - 180.0
+fn x_to_lon(x: f64, zoom: f64) -> f64 {
+    x / (2.0_f64.powf(zoom)) * 360.0 - 180.0
+}
+
+/// Wraps a longitude into the range from -180 to 180 degrees, as the map repeats horizontally.
+fn wrap_lon(lon: f64) -> f64 {
+    (lon + 180.0).rem_euclid(360.0) - 180.0
 }
 
 /// Converts the y-coordinate of a tile to latitude at a given zoom level.
-fn y_to_lat(y: f64, zoom: u8) -> f64 {
-    let n = std::f64::consts::PI - 2.0 * std::f64::consts::PI * y / (2.0_f64.powi(i32::from(zoom)));
+fn y_to_lat(y: f64, zoom: f64) -> f64 {
+    let n = std::f64::consts::PI - 2.0 * std::f64::consts::PI * y / (2.0_f64.powf(zoom));
     n.sinh().atan().to_degrees()
 }
 
-/// Draws the map tiles.
+/// Returns the geographical area covered by a tile.
+pub(crate) fn tile_bounds(tile_id: &TileId) -> GeoBounds {
+    let (x, y, z) = (
+        f64::from(tile_id.x),
+        f64::from(tile_id.y),
+        f64::from(tile_id.z),
+    );
+    GeoBounds::new(
+        GeoPos {
+            lon: x_to_lon(x, z),
+            lat: y_to_lat(y + 1.0, z),
+        },
+        GeoPos {
+            lon: x_to_lon(x + 1.0, z),
+            lat: y_to_lat(y, z),
+        },
+    )
+}
+
+/// Draws the map tiles, and starts loading the ones that are not in memory. Tiles that were not
+/// drawn are left in memory until the caller evicts them.
+///
+/// The tiles are loaded from the center of the projection outwards. `priority_offset` is added to
+/// their priorities, so that the tiles of one view can be loaded after those of another.
+#[expect(clippy::too_many_arguments)]
 pub(crate) fn draw_map(
-    tiles: &mut HashMap<TileId, Tile>,
+    tiles: &mut MemoryCache,
     config: &dyn MapConfig,
+    loader: &TileLoader,
     painter: &egui::Painter,
     projection: &MapProjection,
+    placeholder: &PlaceholderFn,
+    visuals: &egui::Visuals,
+    priority_offset: f32,
 ) {
+    let rotation = ViewRotation::new(projection);
     let visible_tiles: Vec<_> = visible_tiles(projection).collect();
-    for (tile_id, tile_pos) in visible_tiles {
-        load_tile(tiles, config, painter.ctx(), tile_id);
-        draw_tile(tiles, painter, &tile_id, tile_pos, Color32::WHITE);
+    for (tile_id, tile_rect) in visible_tiles {
+        load_tile(
+            tiles,
+            config,
+            loader,
+            painter.ctx(),
+            tile_id,
+            tile_priority(projection, tile_rect) + priority_offset,
+        );
+        draw_tile(
+            tiles,
+            painter,
+            &tile_id,
+            tile_rect,
+            &rotation,
+            Color32::WHITE,
+            placeholder,
+            visuals,
+        );
+    }
+}
+
+/// Returns an iterator over the visible tiles and their rectangles on the screen.
+///
+/// The tiles are of the whole zoom level nearest to the zoom level of the projection, scaled to
+/// the fractional zoom level. The rectangles are those of a north-up view, and are rotated with
+/// [`ViewRotation`] when the map has a bearing.
+pub(crate) fn visible_tiles(projection: &MapProjection) -> impl Iterator<Item = (TileId, Rect)> {
+    let zoom = projection.tile_zoom();
+    let tile_size = projection.tile_size();
+    let center_x = lon_to_x(projection.center_lon, f64::from(zoom));
+    let center_y = lat_to_y(projection.center_lat, f64::from(zoom));
+
+    let widget_center_x = f64::from(projection.widget_rect.width()) / 2.0;
+    let widget_center_y = f64::from(projection.widget_rect.height()) / 2.0;
+
+    // Cover the whole widget, also when it is rotated.
+    let view_size = projection.view_size();
+    let half_view_x = f64::from(view_size.x) / 2.0;
+    let half_view_y = f64::from(view_size.y) / 2.0;
+
+    let x_min = (center_x - half_view_x / tile_size).floor() as i32;
+    let y_min = (center_y - half_view_y / tile_size).floor() as i32;
+    let x_max = (center_x + half_view_x / tile_size).ceil() as i32;
+    let y_max = (center_y + half_view_y / tile_size).ceil() as i32;
+
+    // Both edges of a tile are computed from the tile coordinates, so that scaled tiles line up
+    // without gaps.
+    let rect_min = projection.widget_rect.min;
+    let screen_pos = move |x: i32, y: i32| {
+        rect_min
+            + Vec2::new(
+                (widget_center_x + (f64::from(x) - center_x) * tile_size) as f32,
+                (widget_center_y + (f64::from(y) - center_y) * tile_size) as f32,
+            )
+    };
+    // The world repeats horizontally, but there is nothing above or below it.
+    let tiles_per_side = 1_i64 << zoom.min(32);
+    let y_min = y_min.max(0);
+    let y_max = y_max.min((tiles_per_side - 1) as i32);
+    (x_min..=x_max).flat_map(move |x| {
+        (y_min..=y_max).map(move |y| {
+            let tile_id = TileId {
+                z: zoom,
+                x: i64::from(x).rem_euclid(tiles_per_side) as u32,
+                y: y as u32,
+            };
+            let tile_rect = Rect::from_min_max(screen_pos(x, y), screen_pos(x + 1, y + 1));
+            (tile_id, tile_rect)
+        })
+    })
+}
+
+/// The rotation of the map view by its bearing. Tiles are laid out north-up and rotated around
+/// the center of the widget when they are drawn, so that neighbouring tiles still line up.
+#[derive(Clone, Copy)]
+pub(crate) struct ViewRotation {
+    rotation: Rot2,
+    origin: egui::Pos2,
+}
+
+impl ViewRotation {
+    /// Returns the rotation of the view of a projection.
+    pub(crate) fn new(projection: &MapProjection) -> Self {
+        Self {
+            rotation: projection.rotation(),
+            origin: projection.widget_rect.center(),
+        }
+    }
+
+    /// Draws the `uv` part of a texture in a north-up rectangle, rotated with the view.
+    fn image(
+        &self,
+        painter: &egui::Painter,
+        texture_id: egui::TextureId,
+        rect: Rect,
+        uv: Rect,
+        tint: Color32,
+    ) {
+        if self.rotation == Rot2::IDENTITY {
+            painter.image(texture_id, rect, uv, tint);
+            return;
+        }
+        let mut mesh = egui::Mesh::with_texture(texture_id);
+        mesh.add_rect_with_uv(rect, uv, tint);
+        mesh.rotate(self.rotation, self.origin);
+        painter.add(mesh);
+    }
+
+    /// Moves a north-up rectangle to where its center is in the rotated view.
+    fn place(&self, rect: Rect) -> Rect {
+        let center = self.origin + self.rotation * (rect.center() - self.origin);
+        Rect::from_center_size(center, rect.size())
+    }
+}
+
+/// Returns the download priority of a tile at the given screen rectangle: its distance from the
+/// center of the map in pixels. Tiles with a lower value are downloaded first.
+pub(crate) fn tile_priority(projection: &MapProjection, tile_rect: Rect) -> f32 {
+    tile_rect.center().distance(projection.widget_rect.center())
+}
+
+/// map loads tile as a texture
+pub(crate) fn load_tile(
+    tiles: &mut MemoryCache,
+    config: &dyn MapConfig,
+    loader: &TileLoader,
+    ctx: &egui::Context,
+    tile_id: TileId,
+    priority: f32,
+) {
+    let tile_state = tiles.get_or_insert_with(tile_id, || Tile::Loading {
+        download: spawn_tile_load(config, loader, tile_id, priority),
+        attempts: 0,
+    });
+
+    // Load a failed tile again once its retry delay has passed.
+    if let Tile::Failed {
+        attempts,
+        retry_at: Some(retry_at),
+        ..
+    } = tile_state
+    {
+        let remaining = retry_at.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            let attempts = *attempts;
+            debug!("Retrying tile {tile_id:?}, attempt {}", attempts + 1);
+            *tile_state = Tile::Loading {
+                download: spawn_tile_load(config, loader, tile_id, priority),
+                attempts,
+            };
+        } else {
+            ctx.request_repaint_after(remaining);
+        }
+    }
+
+    // If the tile is loading, check if the download is ready and update the state.
+    // This is done before matching on the state, so that we can immediately draw
+    // the tile if it has just finished loading.
+    if let Tile::Loading { download, attempts } = tile_state {
+        // The map may have moved since the download was queued.
+        download.set_priority(priority);
+
+        if let Some(result) = download.ready() {
+            match result {
+                Ok(color_image) => {
+                    let texture = ctx.load_texture(
+                        format!("tile_{}_{}_{}", tile_id.z, tile_id.x, tile_id.y),
+                        color_image.clone(),
+                        Default::default(),
+                    );
+                    *tile_state = Tile::Loaded(texture);
+                }
+                Err(e) => {
+                    let error = e.clone();
+                    let attempts = *attempts + 1;
+                    let delay = e
+                        .downcast_ref::<MapError>()
+                        .filter(|e| e.is_transient())
+                        .and_then(|_| loader.retry_policy.delay(attempts));
+
+                    // Failures are logged once here, not every time the tile is drawn.
+                    match delay {
+                        Some(delay) => warn!("{e:#}. Retrying in {delay:?}"),
+                        None => error!("{e:?}"),
+                    }
+                    *tile_state = Tile::Failed {
+                        error,
+                        attempts,
+                        retry_at: delay.map(|delay| Instant::now() + delay),
+                    };
+                }
+            }
+        }
+    }
+}
+
+/// Starts loading a tile in the background, from the tile source of the configuration if it has
+/// one and otherwise from the tile server.
+fn spawn_tile_load(
+    config: &dyn MapConfig,
+    loader: &TileLoader,
+    tile_id: TileId,
+    priority: f32,
+) -> Download {
+    let url = tile_id.to_url(config);
+    let source = config.tile_source();
+    // Tiles read from a tile source are not limited per host.
+    let host = if source.is_none() {
+        tile_host(&url)
+    } else {
+        None
+    };
+    let disk_cache = loader
+        .disk_cache
+        .as_ref()
+        .map(|cache| (cache.clone(), config.cache_key()));
+    let offline = loader.offline;
+    let http_client = loader
+        .http_client
+        .clone()
+        .or_else(|| config.http_client())
+        .unwrap_or_default();
+    loader
+        .download_pool
+        .spawn(host, priority, move || -> Result<_, Arc<eyre::Report>> {
+            let result = match &source {
+                Some(source) => read_tile(source.as_ref(), tile_id),
+                None => fetch_tile(&http_client, &url, tile_id, disk_cache.as_ref(), offline)
+                    .map(|tile| tile.image),
+            };
+            let result: Result<_, eyre::Report> = result
+                .map_err(eyre::Report::from)
+                .with_context(|| format!("Failed to load tile from {}", &url));
+
+            result.map_err(Arc::new)
+        })
+}
+
+/// Returns the host that a tile is downloaded from, or `None` for local files.
+pub(crate) fn tile_host(url: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .ok()
+        .filter(|url| url.scheme() != "file")
+        .and_then(|url| url.host_str().map(str::to_string))
+}
+
+/// A tile loaded by [`fetch_tile`].
+struct FetchedTile {
+    image: egui::ColorImage,
+
+    /// Whether this is a stale cached tile that could not be revalidated with the server.
+    stale: bool,
+}
+
+impl FetchedTile {
+    fn decode(bytes: &[u8], stale: bool) -> Result<Self, MapError> {
+        decode_tile(bytes).map(|image| Self { image, stale })
+    }
+}
+
+/// Fetches a tile, using the disk cache if one is given.
+///
+/// Fresh tiles are served straight from the cache. Stale tiles are revalidated with the server
+/// using the stored `ETag` and `Last-Modified` values, and are still served if the server cannot
+/// be reached. When `offline` is set, cached tiles are served regardless of their age and the
+/// server is never contacted.
+fn fetch_tile(
+    http_client: &HttpClient,
+    url: &str,
+    tile_id: TileId,
+    disk_cache: Option<&(DiskCache, String)>,
+    offline: bool,
+) -> Result<FetchedTile, MapError> {
+    let now = SystemTime::now();
+    let cached = disk_cache.and_then(|(cache, provider)| cache.get(provider, &tile_id));
+    if let Some(cached) = &cached
+        && (offline || cached.meta.is_fresh(now))
+    {
+        debug!("Using cached tile for {url}");
+        return FetchedTile::decode(&cached.bytes, false);
+    }
+    if offline {
+        return Err(MapError::TileNotAvailableOffline);
+    }
+
+    let mut request = http_client.client().get(url);
+    if let Some(cached) = &cached {
+        if let Some(etag) = &cached.meta.etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.meta.last_modified {
+            request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    debug!("Downloading tile from {url}");
+    let response = match request.send() {
+        Ok(response) => response,
+        Err(e) => {
+            if let Some(cached) = cached {
+                warn!("Unable to revalidate tile from {url}, using stale cached tile: {e}");
+                return FetchedTile::decode(&cached.bytes, true);
+            }
+            return Err(MapError::from(e));
+        }
+    };
+
+    if response.status() == StatusCode::NOT_MODIFIED
+        && let Some(cached) = cached
+    {
+        if let Some((cache, provider)) = disk_cache
+            && let Some(mut meta) = CacheMeta::from_headers(response.headers(), now, cache.ttl())
+        {
+            // Servers may leave out the validators from a 304 response.
+            meta.etag = meta.etag.or(cached.meta.etag);
+            meta.last_modified = meta.last_modified.or(cached.meta.last_modified);
+            if let Err(e) = cache.put_meta(provider, &tile_id, &meta) {
+                warn!("Unable to update cached tile metadata for {url}: {e}");
+            }
+        }
+        return FetchedTile::decode(&cached.bytes, false);
     }
-}
 
-/// Returns an iterator over the visible tiles.
-pub(crate) fn visible_tiles(
-    projection: &MapProjection,
-) -> impl Iterator<Item = (TileId, egui::Pos2)> {
-    let center_x = lon_to_x(projection.center_lon, projection.zoom);
-    let center_y = lat_to_y(projection.center_lat, projection.zoom);
+    if !response.status().is_success() {
+        return Err(MapError::TileDownloadError(response.status()));
+    }
 
-    let widget_center_x = projection.widget_rect.width() / 2.0;
-    let widget_center_y = projection.widget_rect.height() / 2.0;
+    let headers = response.headers().clone();
+    let bytes = response.bytes().map_err(MapError::from)?.to_vec();
+    let image = decode_tile(&bytes)?;
 
-    let x_min = (center_x - f64::from(widget_center_x) / f64::from(TILE_SIZE)).floor() as i32;
-    let y_min = (center_y - f64::from(widget_center_y) / f64::from(TILE_SIZE)).floor() as i32;
-    let x_max = (center_x + f64::from(widget_center_x) / f64::from(TILE_SIZE)).ceil() as i32;
-    let y_max = (center_y + f64::from(widget_center_y) / f64::from(TILE_SIZE)).ceil() as i32;
+    // Only store tiles that could be decoded, so that a broken response is not served from the
+    // cache until it expires.
+    if let Some((cache, provider)) = disk_cache
+        && let Some(meta) = CacheMeta::from_headers(&headers, now, cache.ttl())
+        && let Err(e) = cache.put(provider, &tile_id, &bytes, &meta)
+    {
+        warn!("Unable to store tile from {url} in the disk cache: {e}");
+    }
 
-    let zoom = projection.zoom;
-    let rect_min = projection.widget_rect.min;
-    (x_min..=x_max).flat_map(move |x| {
-        (y_min..=y_max).map(move |y| {
-            let tile_id = TileId {
-                z: zoom,
-                x: x as u32,
-                y: y as u32,
-            };
-            let screen_x = widget_center_x + (f64::from(x) - center_x) as f32 * TILE_SIZE as f32;
-            let screen_y = widget_center_y + (f64::from(y) - center_y) as f32 * TILE_SIZE as f32;
-            let tile_pos = rect_min + Vec2::new(screen_x, screen_y);
-            (tile_id, tile_pos)
-        })
+    Ok(FetchedTile {
+        image,
+        stale: false,
     })
 }
 
-/// map loads tile as a texture
-pub(crate) fn load_tile(
-    tiles: &mut HashMap<TileId, Tile>,
-    config: &dyn MapConfig,
-    ctx: &egui::Context,
-    tile_id: TileId,
-) {
-    let tile_state = tiles.entry(tile_id).or_insert_with(|| {
-        let url = tile_id.to_url(config);
-        let promise =
-            Promise::spawn_thread("download_tile", move || -> Result<_, Arc<eyre::Report>> {
-                let result: Result<_, eyre::Report> = (|| {
-                    debug!("Downloading tile from {}", &url);
-                    let response = CLIENT.get(&url).send().map_err(MapError::from)?;
-
-                    if !response.status().is_success() {
-                        return Err(MapError::TileDownloadError(response.status().to_string()));
-                    }
-
-                    let bytes = response.bytes().map_err(MapError::from)?.to_vec();
-                    let image = image::load_from_memory(&bytes)
-                        .map_err(MapError::from)?
-                        .to_rgba8();
-
-                    let size = [image.width() as _, image.height() as _];
-                    let pixels = image.into_raw();
-                    Ok(egui::ColorImage::from_rgba_unmultiplied(size, &pixels))
-                })()
-                .with_context(|| format!("Failed to download tile from {}", &url));
+/// Reads a tile from a tile source. Tiles that are missing from the source are transparent.
+fn read_tile(source: &dyn TileSource, tile_id: TileId) -> Result<egui::ColorImage, MapError> {
+    match source.tile_bytes(&tile_id)? {
+        Some(bytes) => decode_tile(&bytes),
+        None => Ok(egui::ColorImage::filled([1, 1], Color32::TRANSPARENT)),
+    }
+}
 
-                result.map_err(Arc::new)
-            });
-        Tile::Loading(promise)
-    });
+/// Decodes the bytes of a tile image.
+fn decode_tile(bytes: &[u8]) -> Result<egui::ColorImage, MapError> {
+    let image = image::load_from_memory(bytes)
+        .map_err(MapError::from)?
+        .to_rgba8();
 
-    // If the tile is loading, check if the promise is ready and update the state.
-    // This is done before matching on the state, so that we can immediately draw
-    // the tile if it has just finished loading.
-    if let Tile::Loading(promise) = tile_state
-        && let Some(result) = promise.ready()
-    {
-        match result {
-            Ok(color_image) => {
-                let texture = ctx.load_texture(
-                    format!("tile_{}_{}_{}", tile_id.z, tile_id.x, tile_id.y),
-                    color_image.clone(),
-                    Default::default(),
-                );
-                *tile_state = Tile::Loaded(texture);
-            }
-            Err(e) => {
-                error!("{e:?}");
-                *tile_state = Tile::Failed(e.clone());
-            }
-        }
-    }
+    let size = [image.width() as _, image.height() as _];
+    let pixels = image.into_raw();
+    Ok(egui::ColorImage::from_rgba_unmultiplied(size, &pixels))
 }
 
-/// Draws a single map tile.
+/// Draws a single map tile in its north-up rectangle, rotated with the view.
+#[expect(clippy::too_many_arguments)]
 pub(crate) fn draw_tile(
-    tiles: &HashMap<TileId, Tile>,
+    tiles: &MemoryCache,
     painter: &egui::Painter,
     tile_id: &TileId,
-    tile_pos: egui::Pos2,
+    tile_rect: Rect,
+    rotation: &ViewRotation,
     tint: Color32,
+    placeholder: &PlaceholderFn,
+    visuals: &egui::Visuals,
 ) {
-    let tile_rect = Rect::from_min_size(tile_pos, Vec2::new(TILE_SIZE as f32, TILE_SIZE as f32));
     let default_state = Tile::Unknown;
     let tile_state = tiles.get(tile_id).unwrap_or(&default_state);
-    match tile_state {
-        Tile::Loading(_) => {
-            // Draw a gray background and a border for the placeholder.
-            painter.rect_filled(tile_rect, 0.0, Color32::from_gray(220));
-            painter.rect_stroke(
-                tile_rect,
-                0.0,
-                egui::Stroke::new(1.0, Color32::GRAY),
-                egui::StrokeKind::Inside,
-            );
-
-            // Draw a question mark in the center.
-            painter.text(
-                tile_rect.center(),
-                egui::Align2::CENTER_CENTER,
-                "⌛",
-                egui::FontId::proportional(40.0),
-                Color32::ORANGE,
-            );
-
+    let status = match tile_state {
+        Tile::Loading { .. } => {
             // The tile is still loading, so we need to tell egui to repaint.
             painter.ctx().request_repaint();
+
+            if draw_fallback_tile(tiles, painter, tile_id, tile_rect, rotation, tint) {
+                return;
+            }
+            TileStatus::Loading
         }
         Tile::Loaded(texture) => {
-            painter.image(
+            rotation.image(
+                painter,
                 texture.id(),
                 tile_rect,
                 Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
                 tint,
             );
+            return;
         }
-        Tile::Failed(e) => {
-            // Draw a gray background and a border for the placeholder.
-            painter.rect_filled(tile_rect, 0.0, Color32::from_gray(220));
-            painter.rect_stroke(
-                tile_rect,
-                0.0,
-                egui::Stroke::new(1.0, Color32::GRAY),
-                egui::StrokeKind::Inside,
-            );
+        Tile::Failed {
+            error, retry_at, ..
+        } => TileStatus::Failed {
+            error,
+            retry_at: *retry_at,
+        },
+        Tile::Unknown => {
+            error!("Tile state not found for {tile_id:?}");
+            TileStatus::Unknown
+        }
+    };
+
+    placeholder(
+        painter,
+        &Placeholder {
+            tile_id: *tile_id,
+            rect: rotation.place(tile_rect),
+            rotation: rotation.rotation,
+            status,
+            visuals,
+        },
+    );
+}
 
-            // Draw a red exclamation mark in the center.
-            painter.text(
-                tile_rect.center(),
-                egui::Align2::CENTER_CENTER,
-                "❌",
-                egui::FontId::proportional(40.0),
-                Color32::RED,
-            );
+/// Shows the error of the failed tile under the mouse pointer in a tooltip.
+pub(crate) fn show_tile_error(
+    tiles: &MemoryCache,
+    response: &Response,
+    projection: &MapProjection,
+) {
+    let Some(pos) = response.hover_pos() else {
+        return;
+    };
+    let geo_pos = projection.unproject(pos);
+    let zoom = projection.tile_zoom();
+    let tiles_per_side = 2.0_f64.powi(i32::from(zoom));
+    let x = lon_to_x(geo_pos.lon, f64::from(zoom))
+        .floor()
+        .rem_euclid(tiles_per_side);
+    let y = lat_to_y(geo_pos.lat, f64::from(zoom)).floor();
+    if !(0.0..tiles_per_side).contains(&y) {
+        return;
+    }
+    let tile_id = TileId {
+        z: zoom,
+        x: x as u32,
+        y: y as u32,
+    };
+    if let Some(Tile::Failed { error, .. }) = tiles.get(&tile_id) {
+        response.clone().on_hover_text(format!("{error:#}"));
+    }
+}
 
-            // Log the error message
-            error!("Failed to load tile: {e:?}");
+/// Draws already loaded tiles of other zoom levels in place of a tile that is still loading.
+///
+/// The closest loaded ancestor is scaled up to cover the tile, and any loaded children of the
+/// tile are drawn on top of it, as they show more detail. Returns `false` if there was nothing
+/// to draw.
+fn draw_fallback_tile(
+    tiles: &MemoryCache,
+    painter: &egui::Painter,
+    tile_id: &TileId,
+    tile_rect: Rect,
+    rotation: &ViewRotation,
+    tint: Color32,
+) -> bool {
+    let mut drawn = false;
+
+    let ancestor = (1..=tile_id.z.min(MAX_FALLBACK_DEPTH)).find_map(|depth| {
+        let parent = TileId {
+            z: tile_id.z - depth,
+            x: tile_id.x >> depth,
+            y: tile_id.y >> depth,
+        };
+        match tiles.get(&parent) {
+            Some(Tile::Loaded(texture)) => Some((texture, depth)),
+            _ => None,
         }
-        Tile::Unknown => {
-            // Draw a gray background and a border for the placeholder.
-            painter.rect_filled(tile_rect, 0.0, Color32::from_gray(220));
-            painter.rect_stroke(
-                tile_rect,
-                0.0,
-                egui::Stroke::new(1.0, Color32::GRAY),
-                egui::StrokeKind::Inside,
-            );
+    });
+    if let Some((texture, depth)) = ancestor {
+        rotation.image(
+            painter,
+            texture.id(),
+            tile_rect,
+            ancestor_uv(tile_id, depth),
+            tint,
+        );
+        drawn = true;
+    }
 
-            // Draw a question mark in the center.
-            painter.text(
-                tile_rect.center(),
-                egui::Align2::CENTER_CENTER,
-                "❓",
-                egui::FontId::proportional(40.0),
-                Color32::RED,
+    let half_size = tile_rect.size() / 2.0;
+    for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+        let (Some(x), Some(y)) = (
+            tile_id.x.checked_mul(2).and_then(|x| x.checked_add(dx)),
+            tile_id.y.checked_mul(2).and_then(|y| y.checked_add(dy)),
+        ) else {
+            continue;
+        };
+        let child = TileId {
+            z: tile_id.z.saturating_add(1),
+            x,
+            y,
+        };
+        if let Some(Tile::Loaded(texture)) = tiles.get(&child) {
+            let min = tile_rect.min + Vec2::new(dx as f32 * half_size.x, dy as f32 * half_size.y);
+            rotation.image(
+                painter,
+                texture.id(),
+                Rect::from_min_size(min, half_size),
+                Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+                tint,
             );
+            drawn = true;
+        }
+    }
 
-            error!("Tile state not found for {tile_id:?}");
+    drawn
+}
+
+/// Returns the part of the ancestor `depth` levels above a tile that covers the tile, in the
+/// texture coordinates of the ancestor.
+fn ancestor_uv(tile_id: &TileId, depth: u8) -> Rect {
+    let scale = 1.0 / (1_u32 << depth) as f32;
+    let mask = (1_u32 << depth) - 1;
+    let min = pos2(
+        (tile_id.x & mask) as f32 * scale,
+        (tile_id.y & mask) as f32 * scale,
+    );
+    Rect::from_min_size(min, Vec2::splat(scale))
+}
+
+/// Draws a crosshair at `pos`, where the pointer is on a linked map.
+fn draw_crosshair(painter: &egui::Painter, pos: egui::Pos2, visuals: &egui::Visuals) {
+    const ARM: f32 = 12.0;
+    let outline = egui::Stroke::new(3.0, visuals.extreme_bg_color);
+    let stroke = egui::Stroke::new(1.0, visuals.strong_text_color());
+    for stroke in [outline, stroke] {
+        painter.line_segment([pos - Vec2::X * ARM, pos + Vec2::X * ARM], stroke);
+        painter.line_segment([pos - Vec2::Y * ARM, pos + Vec2::Y * ARM], stroke);
+    }
+}
+
+/// A function that adds entries to the context menu of the map. It gets the geographical position
+/// where the menu was opened.
+pub type ContextMenuFn = dyn FnMut(&mut egui::Ui, GeoPos);
+
+/// How much space the map widget takes in its parent `Ui`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MapSize {
+    /// A square of one tile in a horizontal layout. In a vertical layout, the available width
+    /// and the height of one tile.
+    #[default]
+    Auto,
+
+    /// A fixed size in points.
+    Exact(Vec2),
+
+    /// All the available space, for example to fill a panel.
+    FillAvailable,
+
+    /// The largest size with the given ratio of width to height that fits the available space.
+    AspectRatio(f32),
+}
+
+impl MapSize {
+    /// Returns the size of the widget when `available` space is left in the parent `Ui`.
+    ///
+    /// Available space that is unbounded, as in a scroll area, is treated as one tile.
+    fn resolve(self, available: Vec2, horizontal: bool) -> Vec2 {
+        let tile = TILE_SIZE as f32;
+        let available = Vec2::new(
+            if available.x.is_finite() {
+                available.x
+            } else {
+                tile
+            },
+            if available.y.is_finite() {
+                available.y
+            } else {
+                tile
+            },
+        );
+        match self {
+            // Give it a minimum size so that it does not become too small
+            // in a horizontal layout. Use tile size as minimum.
+            Self::Auto if horizontal => Vec2::splat(tile),
+            Self::Auto => {
+                // In a vertical layout, we want to fill the available space, but only width
+                Vec2::new(available.x.at_least(tile), tile)
+            }
+            Self::Exact(size) => size,
+            Self::FillAvailable => available,
+            Self::AspectRatio(ratio) => {
+                let ratio = ratio.at_least(f32::EPSILON);
+                let width = available.x.min(available.y * ratio);
+                Vec2::new(width, width / ratio)
+            }
         }
     }
 }
 
 impl Widget for &mut Map {
     fn ui(self, ui: &mut Ui) -> Response {
-        // Give it a minimum size so that it does not become too small
-        // in a horizontal layout. Use tile size as minimum.
-        let desired_size = if ui.layout().main_dir().is_horizontal() {
-            // In a horizontal layout, we want to be a square of a reasonable size.
-            let side = TILE_SIZE as f32;
-            Vec2::splat(side)
-        } else {
-            // In a vertical layout, we want to fill the available space, but only width
-            let mut available_size = ui
-                .available_size()
-                .at_least(Vec2::new(TILE_SIZE as f32, TILE_SIZE as f32));
-            available_size.y = TILE_SIZE as f32;
-            available_size
-        };
+        let desired_size = self
+            .size
+            .resolve(ui.available_size(), ui.layout().main_dir().is_horizontal())
+            .at_least(self.min_size);
 
         let response = ui.allocate_response(desired_size, Sense::drag().union(Sense::click()));
         let rect = response.rect;
 
+        if let Some(id) = self.persistence_id
+            && !self.state_restored
+        {
+            self.state_restored = true;
+            if let Some(state) = ui.data_mut(|data| data.get_persisted::<MapState>(id)) {
+                self.set_state(&state);
+            }
+        }
+
+        self.follow_link(&rect);
+
         // Create a projection for input handling, based on the state before any changes.
-        let input_projection = MapProjection::new(self.zoom, self.center, rect);
+        let input_projection = self.projection(rect);
 
+        let hidden_layers = &self.hidden_layers;
         let mut input_handled_by_layer = false;
-        for layer in self.layers.values_mut() {
+        for (_, layer) in self
+            .layers
+            .iter_mut()
+            .filter(|(key, _)| !hidden_layers.contains(*key))
+        {
             if layer.handle_input(&response, &input_projection) {
                 input_handled_by_layer = true;
                 break; // Stop after the first layer handles the input.
             }
         }
+        for (layer_key, layer) in &mut self.layers {
+            for click in layer.take_feature_clicks() {
+                self.events.push(MapEvent::LayerFeatureClicked {
+                    layer_key: layer_key.clone(),
+                    feature: click.feature,
+                    pos: click.pos,
+                    button: click.button,
+                });
+            }
+        }
+        self.collect_pointer_events(&response, &input_projection, input_handled_by_layer);
+        self.show_context_menu(&response, &input_projection);
 
         if !input_handled_by_layer {
             self.handle_input(ui, &rect, &response);
@@ -643,30 +2252,87 @@ impl Widget for &mut Map {
                 ui.ctx().set_cursor_icon(egui::CursorIcon::Grab);
             }
         }
+        self.handle_minimap_input(ui, rect, response.id);
+
+        self.glide(ui, &rect);
+        self.animate_camera(ui, &rect);
+        self.animate_zoom(ui);
+        self.constrain_to_bounds(&rect);
+
+        if let Some(link) = &mut self.view_link
+            && link.push(self.center, self.zoom)
+        {
+            // Let the other maps follow right away.
+            ui.ctx().request_repaint();
+        }
+
+        let view = (self.center, self.zoom, self.bearing);
+        if view != self.reported_view {
+            self.reported_view = view;
+            self.events.push(MapEvent::ViewChanged {
+                center: self.center,
+                zoom: self.zoom,
+                bearing: self.bearing,
+            });
+        }
 
         // Update mouse position.
         self.mouse_pos = response
             .hover_pos()
             .map(|pos| input_projection.unproject(pos));
+        if let Some(link) = &self.view_link {
+            link.set_cursor(self.mouse_pos);
+        }
 
         // Create a new projection for drawing, with the updated map state.
-        let draw_projection = MapProjection::new(self.zoom, self.center, rect);
+        let draw_projection = self.projection(rect);
 
         let painter = ui.painter_at(rect);
-        painter.rect_filled(rect, 0.0, Color32::from_rgb(220, 220, 220)); // Background
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color); // Background
 
         draw_map(
             &mut self.tiles,
             self.config.as_ref(),
+            &self.loader,
             &painter,
             &draw_projection,
+            self.placeholder.as_ref(),
+            ui.visuals(),
+            0.0,
         );
+        show_tile_error(&self.tiles, &response, &draw_projection);
+
+        for (key, layer) in &self.layers {
+            if !self.hidden_layers.contains(key) {
+                layer.draw(&painter, &draw_projection);
+            }
+        }
 
-        for layer in self.layers.values() {
-            layer.draw(&painter, &draw_projection);
+        if let Some(pos) = self.view_link.as_ref().and_then(LinkedMap::crosshair) {
+            draw_crosshair(&painter, draw_projection.project(pos), ui.visuals());
+        }
+
+        self.draw_minimap(ui, rect, &draw_projection);
+        // The minimap uses the tiles of the map, so they are evicted only after both are drawn.
+        self.tiles.evict();
+
+        // Show that the map has keyboard focus.
+        if response.has_focus() {
+            painter.rect_stroke(
+                rect,
+                0.0,
+                ui.visuals().selection.stroke,
+                egui::StrokeKind::Inside,
+            );
         }
 
         self.draw_attribution(ui, &rect);
+        self.show_controls(ui, rect);
+
+        if let Some(id) = self.persistence_id {
+            let state = self.state();
+            ui.data_mut(|data| data.insert_persisted(id, state));
+        }
 
         response
     }
@@ -676,6 +2342,8 @@ impl Widget for &mut Map {
 mod tests {
     use super::*;
     use crate::config::OpenStreetMapConfig;
+    use egui::{PointerButton, RawInput};
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     const EPSILON: f64 = 1e-9;
 
@@ -683,7 +2351,7 @@ mod tests {
     fn test_coord_conversion_roundtrip() {
         let original_lon = 24.93545;
         let original_lat = 60.16952;
-        let zoom: u8 = 10;
+        let zoom = 10.0;
 
         let x = lon_to_x(original_lon, zoom);
         let y = lat_to_y(original_lat, zoom);
@@ -726,7 +2394,7 @@ mod tests {
         ];
 
         for (y, zoom, expected_lat) in test_cases {
-            assert!((y_to_lat(y, zoom) - expected_lat).abs() < EPSILON);
+            assert!((y_to_lat(y, f64::from(zoom)) - expected_lat).abs() < EPSILON);
         }
     }
 
@@ -749,7 +2417,7 @@ mod tests {
         ];
 
         for (lat, zoom, expected_y) in test_cases {
-            assert!((lat_to_y(lat, zoom) - expected_y).abs() < EPSILON);
+            assert!((lat_to_y(lat, f64::from(zoom)) - expected_y).abs() < EPSILON);
         }
     }
 
@@ -770,7 +2438,7 @@ mod tests {
         ];
 
         for (x, zoom, expected_lon) in test_cases {
-            assert!((x_to_lon(x, zoom) - expected_lon).abs() < EPSILON);
+            assert!((x_to_lon(x, f64::from(zoom)) - expected_lon).abs() < EPSILON);
         }
     }
 
@@ -793,7 +2461,7 @@ mod tests {
         ];
 
         for (lon, zoom, expected_x) in test_cases {
-            assert!((lon_to_x(lon, zoom) - expected_x).abs() < EPSILON);
+            assert!((lon_to_x(lon, f64::from(zoom)) - expected_x).abs() < EPSILON);
         }
     }
 
@@ -809,6 +2477,614 @@ mod tests {
         assert_eq!(url, "https://tile.openstreetmap.org/10/559/330.png");
     }
 
+    #[test]
+    fn test_ancestor_uv() {
+        let tile_id = TileId { z: 3, x: 5, y: 2 };
+        // The parent is (2, 2, 1), and the tile is its bottom right quarter.
+        let uv = ancestor_uv(&tile_id, 1);
+        assert_eq!(uv, Rect::from_min_max(pos2(0.5, 0.0), pos2(1.0, 0.5)));
+        // The grandparent is (1, 1, 0), and the tile is in its second column and third row.
+        let uv = ancestor_uv(&tile_id, 2);
+        assert_eq!(uv, Rect::from_min_max(pos2(0.25, 0.5), pos2(0.5, 0.75)));
+    }
+
+    #[test]
+    fn test_draw_fallback_tile() {
+        let ctx = egui::Context::default();
+        let painter =
+            egui::Painter::new(ctx.clone(), egui::LayerId::background(), Rect::EVERYTHING);
+        let tile_rect = Rect::from_min_size(pos2(0.0, 0.0), Vec2::splat(TILE_SIZE as f32));
+        let projection = MapProjection::new(4.0, GeoPos::from((0.0, 0.0)), tile_rect);
+        let rotation = ViewRotation::new(&projection.with_bearing(30.0));
+        let texture = || {
+            let image = egui::ColorImage::filled([1, 1], Color32::WHITE);
+            Tile::Loaded(ctx.load_texture("tile", image, egui::TextureOptions::default()))
+        };
+
+        let mut tiles = MemoryCache::default();
+        let tile_id = TileId { z: 4, x: 3, y: 7 };
+        assert!(!draw_fallback_tile(
+            &tiles,
+            &painter,
+            &tile_id,
+            tile_rect,
+            &rotation,
+            Color32::WHITE
+        ));
+
+        // A loaded grandparent covers the tile.
+        tiles.get_or_insert_with(TileId { z: 2, x: 0, y: 1 }, texture);
+        assert!(draw_fallback_tile(
+            &tiles,
+            &painter,
+            &tile_id,
+            tile_rect,
+            &rotation,
+            Color32::WHITE
+        ));
+
+        // So does a single loaded child.
+        let mut tiles = MemoryCache::default();
+        tiles.get_or_insert_with(TileId { z: 5, x: 7, y: 14 }, texture);
+        assert!(draw_fallback_tile(
+            &tiles,
+            &painter,
+            &tile_id,
+            tile_rect,
+            &rotation,
+            Color32::WHITE
+        ));
+    }
+
+    #[test]
+    fn test_draw_tile_placeholder() {
+        let ctx = egui::Context::default();
+        let painter = egui::Painter::new(ctx, egui::LayerId::background(), Rect::EVERYTHING);
+        let visuals = egui::Visuals::dark();
+        let tile_id = TileId { z: 2, x: 1, y: 1 };
+        let tile_rect = Rect::from_min_size(pos2(0.0, 0.0), Vec2::splat(TILE_SIZE as f32));
+        let projection = MapProjection::new(2.0, GeoPos::from((0.0, 0.0)), tile_rect);
+
+        let mut tiles = MemoryCache::default();
+        tiles.get_or_insert_with(tile_id, || Tile::Failed {
+            error: Arc::new(eyre::eyre!("Tile server is down")),
+            attempts: 1,
+            retry_at: None,
+        });
+
+        let drawn = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let recorder = drawn.clone();
+        let placeholder = move |_: &egui::Painter, placeholder: &Placeholder<'_>| {
+            if let TileStatus::Failed { error, .. } = placeholder.status {
+                recorder
+                    .borrow_mut()
+                    .push((placeholder.tile_id, error.to_string()));
+            }
+        };
+        draw_tile(
+            &tiles,
+            &painter,
+            &tile_id,
+            tile_rect,
+            &ViewRotation::new(&projection),
+            Color32::WHITE,
+            &placeholder,
+            &visuals,
+        );
+        assert_eq!(
+            *drawn.borrow(),
+            vec![(tile_id, "Tile server is down".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_transient_errors() {
+        assert!(MapError::TileDownloadError(StatusCode::SERVICE_UNAVAILABLE).is_transient());
+        assert!(MapError::TileDownloadError(StatusCode::TOO_MANY_REQUESTS).is_transient());
+        assert!(!MapError::TileDownloadError(StatusCode::NOT_FOUND).is_transient());
+        assert!(!MapError::TileNotAvailableOffline.is_transient());
+
+        // The error is found behind the context added by `load_tile`.
+        let report = eyre::Report::from(MapError::TileDownloadError(StatusCode::BAD_GATEWAY))
+            .wrap_err("Failed to load tile");
+        assert!(
+            report
+                .downcast_ref::<MapError>()
+                .is_some_and(MapError::is_transient)
+        );
+    }
+
+    #[test]
+    fn test_tile_bounds() {
+        let bounds = tile_bounds(&TileId { z: 1, x: 1, y: 0 });
+        assert!((bounds.min.lon - 0.0).abs() < EPSILON);
+        assert!((bounds.max.lon - 180.0).abs() < EPSILON);
+        assert!((bounds.min.lat - 0.0).abs() < EPSILON);
+        assert!((bounds.max.lat - 85.0511287798066).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_visible_tiles_fractional_zoom() {
+        let widget_rect = Rect::from_min_size(pos2(0.0, 0.0), Vec2::splat(512.0));
+        let projection = MapProjection::new(1.6, GeoPos { lon: 0.0, lat: 0.0 }, widget_rect);
+        let tiles: Vec<_> = visible_tiles(&projection).collect();
+
+        // The tiles of level 2 are scaled down to the size of level 1.6.
+        let tile_size = 256.0 * 2.0_f32.powf(-0.4);
+        assert!(tiles.iter().all(|(tile_id, _)| tile_id.z == 2));
+        for (_, tile_rect) in &tiles {
+            assert!((tile_rect.width() - tile_size).abs() < 1e-3);
+        }
+
+        // The tiles cover the whole widget.
+        let covered = tiles.iter().fold(Rect::NOTHING, |covered, (_, tile_rect)| {
+            covered.union(*tile_rect)
+        });
+        assert!(covered.contains_rect(widget_rect));
+    }
+
+    #[test]
+    fn test_visible_tiles_wrap_around_world() {
+        let widget_rect = Rect::from_min_size(pos2(0.0, 0.0), Vec2::new(1024.0, 1024.0));
+        let projection = MapProjection::new(
+            1.0,
+            GeoPos {
+                lon: 180.0,
+                lat: 0.0,
+            },
+            widget_rect,
+        );
+        let tiles: Vec<_> = visible_tiles(&projection).collect();
+
+        // Both sides of the antimeridian are shown, but nothing above or below the world.
+        assert!(
+            tiles
+                .iter()
+                .all(|(tile_id, _)| tile_id.x < 2 && tile_id.y < 2)
+        );
+        let west_of_center = tiles
+            .iter()
+            .find(|(_, tile_rect)| tile_rect.contains(pos2(500.0, 600.0)))
+            .unwrap();
+        assert_eq!(west_of_center.0, TileId { z: 1, x: 1, y: 1 });
+        let east_of_center = tiles
+            .iter()
+            .find(|(_, tile_rect)| tile_rect.contains(pos2(524.0, 600.0)))
+            .unwrap();
+        assert_eq!(east_of_center.0, TileId { z: 1, x: 0, y: 1 });
+    }
+
+    #[test]
+    fn test_visible_tiles_rotated() {
+        let widget_rect = Rect::from_min_size(pos2(0.0, 0.0), Vec2::new(800.0, 200.0));
+        let projection = MapProjection::new(4.0, GeoPos { lon: 0.0, lat: 0.0 }, widget_rect);
+        let rotated: Vec<_> = visible_tiles(&projection.with_bearing(90.0)).collect();
+
+        // Turned sideways, the wide widget needs tiles far above and below the center.
+        let covered = rotated
+            .iter()
+            .fold(Rect::NOTHING, |covered, (_, tile_rect)| {
+                covered.union(*tile_rect)
+            });
+        assert!(covered.contains_rect(Rect::from_center_size(
+            widget_rect.center(),
+            Vec2::new(200.0, 800.0)
+        )));
+    }
+
+    #[test]
+    fn test_zoom_towards_limits() {
+        let mut map = Map::new(OpenStreetMapConfig::default());
+        map.zoom = 3.0;
+        let rect = Rect::from_min_size(pos2(0.0, 0.0), Vec2::splat(512.0));
+
+        // The world is 512 points wide at zoom level 1, so the map does not zoom out further.
+        map.zoom_towards(-5.0, rect.center(), &rect);
+        assert_eq!(map.zoom_target(), 1.0);
+
+        map.zoom_towards(25.0, rect.center(), &rect);
+        assert_eq!(map.zoom_target(), 19.0);
+    }
+
+    #[test]
+    fn test_pinch_keeps_point_under_fingers() {
+        let mut map = Map::new(OpenStreetMapConfig::default());
+        map.center = (24.94, 60.17).into();
+        map.zoom = 10.0;
+        let rect = Rect::from_min_size(pos2(0.0, 0.0), Vec2::new(800.0, 600.0));
+        let from = pos2(300.0, 200.0);
+        let geo_pos = MapProjection::new(map.zoom, map.center, rect).unproject(from);
+
+        // Spread the fingers and move them to the right.
+        let to = pos2(350.0, 200.0);
+        map.pinch(11.5, 0.0, from, to, &rect);
+        assert_eq!(map.zoom, 11.5);
+        let moved = MapProjection::new(map.zoom, map.center, rect).project(geo_pos);
+        assert!((moved - to).length() < 1e-3);
+
+        // The zoom level stays within the limits.
+        map.pinch(25.0, 0.0, to, to, &rect);
+        assert_eq!(map.zoom, 19.0);
+
+        // Turning the fingers rotates the map around them.
+        let geo_pos = map.projection(rect).unproject(to);
+        map.pinch(19.0, 45.0, to, to, &rect);
+        assert_eq!(map.bearing, 45.0);
+        let moved = map.projection(rect).project(geo_pos);
+        assert!((moved - to).length() < 1e-3);
+    }
+
+    #[test]
+    fn test_max_bounds() {
+        let finland = GeoBounds::new((20.5, 59.7).into(), (31.6, 70.1).into());
+        let mut map = Map::new(OpenStreetMapConfig::default()).with_max_bounds(finland);
+        let rect = Rect::from_min_size(pos2(0.0, 0.0), Vec2::new(800.0, 600.0));
+
+        // The map cannot be zoomed out past where Finland fills the widget.
+        map.zoom_towards(0.0, rect.center(), &rect);
+        let lowest = map.zoom_target();
+        assert!(lowest > 4.0);
+
+        // Nor panned away from Finland.
+        map.zoom = lowest;
+        map.zoom_animation = None;
+        map.pan_by(Vec2::new(5000.0, 5000.0), &rect);
+        map.constrain_to_bounds(&rect);
+        let projection = map.projection(rect);
+        let view = GeoBounds::new(
+            projection.unproject(rect.left_top()),
+            projection.unproject(rect.right_bottom()),
+        );
+        assert!(view.min.lon >= finland.min.lon - 1e-6);
+        assert!(view.min.lat >= finland.min.lat - 1e-6);
+        assert!(view.max.lon <= finland.max.lon + 1e-6);
+        assert!(view.max.lat <= finland.max.lat + 1e-6);
+
+        // Programmatic moves are kept inside the bounds as well.
+        map.center = (0.0, 0.0).into();
+        map.zoom = 1.0;
+        map.constrain_to_bounds(&rect);
+        assert!((map.zoom - lowest).abs() < 1e-9);
+        assert!(finland.contains(map.center));
+
+        // Hitting the edge stops kinetic panning.
+        map.pan_velocity = Vec2::new(100.0, 0.0);
+        map.center = (0.0, 65.0).into();
+        map.constrain_to_bounds(&rect);
+        assert_eq!(map.pan_velocity, Vec2::ZERO);
+    }
+
+    #[test]
+    fn test_max_bounds_across_antimeridian() {
+        // Fiji, from 170° east to 170° west.
+        let fiji = GeoBounds::from_corners((170.0, -22.0).into(), (-170.0, -12.0).into());
+        let mut map = Map::new(OpenStreetMapConfig::default()).with_max_bounds(fiji);
+        let rect = Rect::from_min_size(pos2(0.0, 0.0), Vec2::new(800.0, 600.0));
+
+        // The box is 20 degrees wide, not 340 degrees.
+        let lowest = map.bounds_zoom(&rect).unwrap();
+        assert!(lowest > 5.0 && lowest < 8.0);
+
+        map.center = (0.0, -17.0).into();
+        map.zoom = 8.0;
+        map.constrain_to_bounds(&rect);
+        assert!(map.center.lon >= 170.0 || map.center.lon <= -170.0);
+
+        // A view across the antimeridian stays where it is.
+        map.center = (-179.0, -17.0).into();
+        map.constrain_to_bounds(&rect);
+        assert_eq!(map.center, GeoPos::from((-179.0, -17.0)));
+    }
+
+    #[test]
+    fn test_map_size() {
+        let available = Vec2::new(800.0, 600.0);
+        let tile = TILE_SIZE as f32;
+        assert_eq!(MapSize::Auto.resolve(available, true), Vec2::splat(tile));
+        assert_eq!(
+            MapSize::Auto.resolve(available, false),
+            Vec2::new(800.0, tile)
+        );
+        assert_eq!(
+            MapSize::Exact(Vec2::new(300.0, 200.0)).resolve(available, false),
+            Vec2::new(300.0, 200.0)
+        );
+        assert_eq!(MapSize::FillAvailable.resolve(available, true), available);
+        assert_eq!(
+            MapSize::AspectRatio(2.0).resolve(available, false),
+            Vec2::new(800.0, 400.0)
+        );
+        assert_eq!(
+            MapSize::AspectRatio(0.5).resolve(available, false),
+            Vec2::new(300.0, 600.0)
+        );
+
+        // Unbounded space, as in a scroll area, is treated as one tile.
+        assert_eq!(
+            MapSize::FillAvailable.resolve(Vec2::new(800.0, f32::INFINITY), false),
+            Vec2::new(800.0, tile)
+        );
+    }
+
+    #[test]
+    fn test_view_changed_event() {
+        let ctx = egui::Context::default();
+        let mut map = Map::new(OpenStreetMapConfig::default()).with_events(true);
+        map.set_offline(true);
+        let show = |map: &mut Map| {
+            let _ = ctx.run_ui(RawInput::default(), |ui| {
+                ui.add(&mut *map);
+            });
+        };
+
+        show(&mut map);
+        assert!(map.take_events().is_empty());
+
+        map.center = (24.9, 60.2).into();
+        map.zoom = 10.0;
+        show(&mut map);
+        assert_eq!(
+            map.take_events(),
+            vec![MapEvent::ViewChanged {
+                center: (24.9, 60.2).into(),
+                zoom: 10.0,
+                bearing: 0.0,
+            }]
+        );
+
+        show(&mut map);
+        assert!(map.take_events().is_empty());
+    }
+
+    /// A layer that only has an opacity.
+    #[derive(Default)]
+    struct OpacityLayer {
+        opacity: f32,
+    }
+
+    impl Layer for OpacityLayer {
+        fn handle_input(&mut self, _: &Response, _: &MapProjection) -> bool {
+            false
+        }
+        fn draw(&self, _: &egui::Painter, _: &MapProjection) {}
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+        fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+            self
+        }
+        fn opacity(&self) -> f32 {
+            self.opacity
+        }
+        fn set_opacity(&mut self, opacity: f32) {
+            self.opacity = opacity;
+        }
+    }
+
+    /// A layer with a single context menu entry, which counts how often it is shown.
+    struct MenuLayer {
+        shown: Arc<AtomicUsize>,
+    }
+
+    impl Layer for MenuLayer {
+        fn handle_input(&mut self, _: &Response, _: &MapProjection) -> bool {
+            false
+        }
+        fn draw(&self, _: &egui::Painter, _: &MapProjection) {}
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+        fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+            self
+        }
+        fn context_menu(&mut self, ui: &mut Ui, _: GeoPos, _: &MapProjection) {
+            self.shown.fetch_add(1, Ordering::Relaxed);
+            let _ = ui.button("Layer entry");
+        }
+    }
+
+    #[test]
+    fn test_layer_context_menu() {
+        let ctx = egui::Context::default();
+        let shown = Arc::new(AtomicUsize::new(0));
+        let mut map = Map::new(OpenStreetMapConfig::default()).with_offline(true);
+        map.add_layer(
+            "menu",
+            MenuLayer {
+                shown: shown.clone(),
+            },
+        );
+        let pos = pos2(100.0, 100.0);
+        let click = |pressed| egui::Event::PointerButton {
+            pos,
+            button: PointerButton::Secondary,
+            pressed,
+            modifiers: egui::Modifiers::NONE,
+        };
+        let frames = [
+            vec![egui::Event::PointerMoved(pos)],
+            vec![click(true)],
+            vec![click(false)],
+        ];
+        for events in frames {
+            let input = RawInput {
+                events,
+                ..RawInput::default()
+            };
+            let _ = ctx.run_ui(input, |ui| {
+                ui.add(&mut map);
+            });
+        }
+
+        // The menu opens for the entries of the layer without any application entries.
+        assert!(shown.load(Ordering::Relaxed) > 0);
+        assert!(map.context_menu_pos.is_some());
+    }
+
+    #[test]
+    fn test_map_state() {
+        let mut map = Map::new(OpenStreetMapConfig::default());
+        map.add_layer("roads", OpacityLayer { opacity: 0.5 });
+        map.add_layer("labels", OpacityLayer::default());
+        map.set_layer_visible("labels", false);
+        map.set_layer_visible("missing", false);
+        assert!(map.is_layer_visible("roads"));
+        assert!(!map.is_layer_visible("labels"));
+        assert!(!map.is_layer_visible("missing"));
+        map.center = (24.9, 60.2).into();
+        map.zoom = 12.5;
+        map.bearing = 30.0;
+        let state = map.state();
+
+        let mut restored = Map::new(OpenStreetMapConfig::default());
+        restored.add_layer("roads", OpacityLayer::default());
+        restored.add_layer("labels", OpacityLayer::default());
+        restored.set_state(&state);
+        assert_eq!(restored.state(), state);
+
+        // The zoom level is kept within the limits of the configuration.
+        restored.set_state(&MapState {
+            zoom: 100.0,
+            ..state
+        });
+        assert_eq!(restored.zoom, f64::from(MAX_ZOOM));
+    }
+
+    #[test]
+    fn test_map_persistence() {
+        let ctx = egui::Context::default();
+        let id = egui::Id::new("map");
+        let show = |map: &mut Map| {
+            let _ = ctx.run_ui(RawInput::default(), |ui| {
+                ui.add(&mut *map);
+            });
+        };
+
+        let mut map = Map::new(OpenStreetMapConfig::default()).with_persistence(id);
+        map.set_offline(true);
+        show(&mut map);
+        map.center = (24.9, 60.2).into();
+        map.zoom = 10.0;
+        show(&mut map);
+
+        let mut restored = Map::new(OpenStreetMapConfig::default()).with_persistence(id);
+        restored.set_offline(true);
+        show(&mut restored);
+        assert_eq!(restored.center, map.center);
+        assert_eq!(restored.zoom, 10.0);
+    }
+
+    #[test]
+    fn test_linked_maps() {
+        let ctx = egui::Context::default();
+        let show = |map: &mut Map| {
+            let _ = ctx.run_ui(RawInput::default(), |ui| {
+                ui.add(&mut *map);
+            });
+        };
+        let link = ViewLink::new();
+        let mut first = Map::new(OpenStreetMapConfig::default())
+            .with_offline(true)
+            .with_view_link(&link, LinkOptions::default());
+        let mut second = Map::new(OpenStreetMapConfig::default())
+            .with_offline(true)
+            .with_view_link(
+                &link,
+                LinkOptions {
+                    mode: crate::link::LinkMode::Center,
+                    ..LinkOptions::default()
+                },
+            );
+
+        // The first map to be shown sets the linked view.
+        first.center = (24.9, 60.2).into();
+        show(&mut first);
+        show(&mut second);
+        assert_eq!(second.center, first.center);
+
+        // The second map links only the center.
+        second.center = (10.0, 50.0).into();
+        second.zoom = 3.0;
+        show(&mut second);
+        show(&mut first);
+        assert_eq!(first.center, second.center);
+        assert_ne!(first.zoom, 3.0);
+
+        // A map that is zoomed further in stops at its highest zoom level, and does not zoom the
+        // other maps out.
+        let mut closer = Map::new(OpenStreetMapConfig::default())
+            .with_offline(true)
+            .with_view_link(
+                &link,
+                LinkOptions {
+                    zoom_offset: 2.0,
+                    ..LinkOptions::default()
+                },
+            );
+        let max_zoom = f64::from(MAX_ZOOM);
+        first.zoom = max_zoom;
+        show(&mut first);
+        show(&mut closer);
+        assert_eq!(closer.zoom, max_zoom);
+        show(&mut first);
+        assert_eq!(first.zoom, max_zoom);
+        assert_eq!(link.zoom(), Some(max_zoom));
+    }
+
+    #[test]
+    fn test_map_controls() {
+        let ctx = egui::Context::default();
+        let mut map = Map::new(OpenStreetMapConfig::default())
+            .with_offline(true)
+            .with_control(egui::Align2::RIGHT_TOP, crate::controls::ZoomButtons)
+            .with_control(egui::Align2::RIGHT_TOP, crate::controls::CompassButton)
+            .with_control(
+                egui::Align2::LEFT_BOTTOM,
+                crate::controls::LayerSwitcher::default(),
+            )
+            .with_minimap(Minimap {
+                anchor: egui::Align2::RIGHT_TOP,
+                ..Minimap::default()
+            });
+        let show = |map: &mut Map, events| {
+            let input = RawInput {
+                events,
+                ..RawInput::default()
+            };
+            let mut rect = Rect::NOTHING;
+            let _ = ctx.run_ui(input, |ui| {
+                rect = ui.add(&mut *map).rect;
+            });
+            rect
+        };
+        let rect = show(&mut map, Vec::new());
+        assert_eq!(map.controls.len(), 3);
+
+        // The controls are placed below the minimap and above the attribution.
+        let minimap_rect = map.minimap().unwrap().rect(rect);
+        let top_right = map.controls_rect(rect, egui::Align2::RIGHT_TOP);
+        assert!(top_right.min.y > minimap_rect.max.y);
+        let bottom_left = map.controls_rect(rect, egui::Align2::LEFT_BOTTOM);
+        assert!(bottom_left.max.y < rect.max.y - ATTRIBUTION_HEIGHT);
+
+        // Clicking the zoom in button, the first one in the top right corner, zooms the map in.
+        let zoom = map.zoom_target().round();
+        let pos = top_right.right_top() + Vec2::new(-16.0, 16.0);
+        let click = |pressed| egui::Event::PointerButton {
+            pos,
+            button: PointerButton::Primary,
+            pressed,
+            modifiers: egui::Modifiers::NONE,
+        };
+        show(&mut map, vec![egui::Event::PointerMoved(pos)]);
+        show(&mut map, vec![click(true)]);
+        show(&mut map, vec![click(false)]);
+        assert_eq!(map.zoom_target(), zoom + 1.0);
+
+        map.clear_controls();
+        assert!(map.controls.is_empty());
+    }
+
     #[test]
     fn test_map_new() {
         let config = OpenStreetMapConfig::default();
@@ -818,9 +3094,9 @@ mod tests {
         let map = Map::new(config);
 
         assert_eq!(map.center, default_center.into());
-        assert_eq!(map.zoom, default_zoom);
+        assert_eq!(map.zoom, f64::from(default_zoom));
         assert!(map.mouse_pos.is_none());
-        assert!(map.tiles.is_empty());
+        assert_eq!(map.memory_cache_stats().tiles, 0);
     }
 
     #[test]
@@ -828,6 +3104,6 @@ mod tests {
     fn test_map_invalid_zoom_limits() {
         let config = OpenStreetMapConfig::default().min_zoom(15).max_zoom(5);
         let map = Map::new(config);
-        assert_eq!(map.zoom, 15);
+        assert_eq!(map.zoom, 15.0);
     }
 }
@@ -0,0 +1,286 @@
+//! Linking the views of several maps.
+//!
+//! Maps that share a [`ViewLink`] pan and zoom in lockstep: when the view of one of them changes,
+//! the others follow in the next frame. Each map chooses with [`LinkOptions`] what it follows,
+//! and can show a crosshair where the pointer is on another map.
+//!
+//! # Example
+//!
+//! ```
+//! use egui_map_view::{
+//!     Map,
+//!     config::OpenStreetMapConfig,
+//!     link::{LinkMode, LinkOptions, ViewLink},
+//! };
+//!
+//! let link = ViewLink::new();
+//! let photo =
+//!     Map::new(OpenStreetMapConfig::default()).with_view_link(&link, LinkOptions::default());
+//! // An overview that follows the center three zoom levels further out.
+//! let overview = Map::new(OpenStreetMapConfig::default()).with_view_link(
+//!     &link,
+//!     LinkOptions {
+//!         mode: LinkMode::Both,
+//!         zoom_offset: -3.0,
+//!         crosshair: true,
+//!     },
+//! );
+//! ```
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::projection::GeoPos;
+
+/// What a linked map follows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LinkMode {
+    /// Only the center.
+    Center,
+
+    /// Only the zoom level.
+    Zoom,
+
+    /// Both the center and the zoom level.
+    #[default]
+    Both,
+}
+
+/// How a map is linked to the other maps of a [`ViewLink`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinkOptions {
+    /// What the map follows.
+    pub mode: LinkMode,
+
+    /// How many levels the zoom level of this map is above the linked zoom level. Negative values
+    /// zoom the map further out than the other maps.
+    pub zoom_offset: f64,
+
+    /// Whether to show a crosshair where the pointer is on another linked map.
+    pub crosshair: bool,
+}
+
+impl Default for LinkOptions {
+    fn default() -> Self {
+        Self {
+            mode: LinkMode::Both,
+            zoom_offset: 0.0,
+            crosshair: false,
+        }
+    }
+}
+
+/// A view that several maps share. Clones of the handle refer to the same view.
+#[derive(Clone, Debug, Default)]
+pub struct ViewLink {
+    shared: Arc<Mutex<Shared>>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    /// The linked center, if a map has set it.
+    center: Option<GeoPos>,
+
+    /// The linked zoom level, without any offsets, if a map has set it.
+    zoom: Option<f64>,
+
+    /// Grows every time the linked view changes.
+    version: u64,
+
+    /// The number of maps that have subscribed, which gives every map its own id.
+    subscribers: usize,
+
+    /// The map the pointer is on, and where.
+    cursor: Option<(usize, GeoPos)>,
+}
+
+impl ViewLink {
+    /// Creates a new link that no map follows yet.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the linked center, if any map has set it.
+    #[must_use]
+    pub fn center(&self) -> Option<GeoPos> {
+        self.lock().center
+    }
+
+    /// Returns the linked zoom level, if any map has set it.
+    #[must_use]
+    pub fn zoom(&self) -> Option<f64> {
+        self.lock().zoom
+    }
+
+    /// Moves all the linked maps to a center and zoom level.
+    pub fn set_view(&self, center: GeoPos, zoom: f64) {
+        let mut shared = self.lock();
+        shared.center = Some(center);
+        shared.zoom = Some(zoom);
+        shared.version += 1;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        self.shared.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The link of a single map.
+pub(crate) struct LinkedMap {
+    link: ViewLink,
+    options: LinkOptions,
+
+    /// Tells the maps of the link apart.
+    id: usize,
+
+    /// The version of the linked view that the map has seen.
+    version: u64,
+
+    /// The center and zoom level of the map when it last followed or set the linked view.
+    synced: Option<(GeoPos, f64)>,
+}
+
+impl LinkedMap {
+    pub(crate) fn new(link: &ViewLink, options: LinkOptions) -> Self {
+        let mut shared = link.lock();
+        let id = shared.subscribers;
+        shared.subscribers += 1;
+        Self {
+            link: link.clone(),
+            options,
+            id,
+            // A map that is linked later follows the view the other maps have set.
+            version: 0,
+            synced: None,
+        }
+    }
+
+    /// Returns the center and zoom level the map at `center` and `zoom` should move to, if
+    /// another map has changed the linked view since the last call.
+    pub(crate) fn pull(&mut self, center: GeoPos, zoom: f64) -> Option<(GeoPos, f64)> {
+        let shared = self.link.lock();
+        if shared.version == self.version {
+            return None;
+        }
+        self.version = shared.version;
+        let center = match shared.center {
+            Some(linked) if self.options.mode != LinkMode::Zoom => linked,
+            _ => center,
+        };
+        let zoom = match shared.zoom {
+            Some(linked) if self.options.mode != LinkMode::Center => {
+                linked + self.options.zoom_offset
+            }
+            _ => zoom,
+        };
+        self.synced = Some((center, zoom));
+        Some((center, zoom))
+    }
+
+    /// Records where the map ended up after following the linked view, for example when its zoom
+    /// levels or bounds did not let it follow all the way, so that the map does not set the
+    /// linked view to it.
+    pub(crate) fn mark_synced(&mut self, center: GeoPos, zoom: f64) {
+        self.synced = Some((center, zoom));
+    }
+
+    /// Sets the linked view to the view of the map, if the map has moved since it last followed
+    /// or set the linked view. Returns `true` if the linked view changed.
+    pub(crate) fn push(&mut self, center: GeoPos, zoom: f64) -> bool {
+        let changed = self.synced.is_none_or(|(synced_center, synced_zoom)| {
+            (self.options.mode != LinkMode::Zoom && synced_center != center)
+                || (self.options.mode != LinkMode::Center && synced_zoom != zoom)
+        });
+        self.synced = Some((center, zoom));
+        if !changed {
+            return false;
+        }
+
+        let mut shared = self.link.lock();
+        if self.options.mode != LinkMode::Zoom {
+            shared.center = Some(center);
+        }
+        if self.options.mode != LinkMode::Center {
+            shared.zoom = Some(zoom - self.options.zoom_offset);
+        }
+        shared.version += 1;
+        self.version = shared.version;
+        true
+    }
+
+    /// Tells the other maps where the pointer is on this map, if it is on it.
+    pub(crate) fn set_cursor(&self, pos: Option<GeoPos>) {
+        let mut shared = self.link.lock();
+        match pos {
+            Some(pos) => shared.cursor = Some((self.id, pos)),
+            None if shared.cursor.is_some_and(|(id, _)| id == self.id) => shared.cursor = None,
+            None => {}
+        }
+    }
+
+    /// Returns where the pointer is on another map, if the map shows a crosshair for it.
+    pub(crate) fn crosshair(&self) -> Option<GeoPos> {
+        if !self.options.crosshair {
+            return None;
+        }
+        self.link
+            .lock()
+            .cursor
+            .filter(|(id, _)| *id != self.id)
+            .map(|(_, pos)| pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linked_maps_follow_each_other() {
+        let link = ViewLink::new();
+        let mut both = LinkedMap::new(&link, LinkOptions::default());
+        let mut zoom_only = LinkedMap::new(
+            &link,
+            LinkOptions {
+                mode: LinkMode::Zoom,
+                zoom_offset: -2.0,
+                crosshair: true,
+            },
+        );
+        let origin = GeoPos::from((0.0, 0.0));
+        assert_eq!(both.pull(origin, 1.0), None);
+
+        let center = GeoPos::from((24.9, 60.2));
+        assert!(both.push(center, 10.0));
+        assert!(!both.push(center, 10.0));
+        assert_eq!(both.pull(center, 10.0), None);
+        assert_eq!(zoom_only.pull(origin, 1.0), Some((origin, 8.0)));
+        assert_eq!(zoom_only.pull(origin, 8.0), None);
+
+        // A map that is linked later follows the view that has been set.
+        let mut later = LinkedMap::new(&link, LinkOptions::default());
+        assert_eq!(later.pull(origin, 1.0), Some((center, 10.0)));
+
+        // Moving the center does not change the linked zoom level, and the offset is taken away
+        // again when the map sets it.
+        assert!(!zoom_only.push(center, 8.0));
+        assert!(zoom_only.push(center, 5.0));
+        assert_eq!(both.pull(center, 10.0), Some((center, 7.0)));
+
+        // A map that could not follow all the way does not push its own view back.
+        link.set_view(center, 19.0);
+        assert_eq!(zoom_only.pull(center, 5.0), Some((center, 17.0)));
+        zoom_only.mark_synced(center, 16.0);
+        assert!(!zoom_only.push(center, 16.0));
+        assert_eq!(link.zoom(), Some(19.0));
+
+        // Only the other maps show a crosshair for the pointer, and only if they want one.
+        both.set_cursor(Some(center));
+        assert_eq!(zoom_only.crosshair(), Some(center));
+        assert_eq!(both.crosshair(), None);
+        zoom_only.set_cursor(None);
+        assert_eq!(zoom_only.crosshair(), Some(center));
+        both.set_cursor(None);
+        assert_eq!(zoom_only.crosshair(), None);
+    }
+}
@@ -0,0 +1,123 @@
+//! An overview map in a corner of the map.
+//!
+//! The minimap shows the surroundings of the view at a lower zoom level, with the extent of the
+//! view drawn as a rectangle. Dragging the minimap pans the map, and clicking it moves the map
+//! to the clicked point. It draws the tiles of the map from the same cache, but at its own zoom
+//! level, so it loads tiles that the map does not show. They are loaded after the tiles of the
+//! map.
+//!
+//! # Example
+//!
+//! ```
+//! use egui::{Align2, vec2};
+//! use egui_map_view::{Map, config::OpenStreetMapConfig, minimap::Minimap};
+//!
+//! let map = Map::new(OpenStreetMapConfig::default()).with_minimap(Minimap {
+//!     size: vec2(200.0, 150.0),
+//!     anchor: Align2::RIGHT_TOP,
+//!     ..Minimap::default()
+//! });
+//! ```
+
+use egui::{Align2, Pos2, Rect, Vec2, vec2};
+
+use crate::projection::{GeoPos, MapProjection};
+
+/// The look and placement of the minimap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Minimap {
+    /// The size of the minimap. It is made smaller if the map is not big enough for it.
+    pub size: Vec2,
+
+    /// How many levels the minimap is zoomed in from the map. Negative values zoom it out.
+    pub zoom_offset: f64,
+
+    /// The corner of the map the minimap is in.
+    pub anchor: Align2,
+
+    /// The space between the minimap and the edges of the map.
+    pub margin: f32,
+}
+
+impl Default for Minimap {
+    fn default() -> Self {
+        Self {
+            size: vec2(160.0, 120.0),
+            zoom_offset: -4.0,
+            // The attribution is in the bottom left corner.
+            anchor: Align2::RIGHT_BOTTOM,
+            margin: 10.0,
+        }
+    }
+}
+
+impl Minimap {
+    /// Returns where the minimap is in a map that covers `map_rect`.
+    pub(crate) fn rect(&self, map_rect: Rect) -> Rect {
+        let inner = map_rect.shrink(self.margin);
+        let size = self.size.min(inner.size()).max(Vec2::ZERO);
+        self.anchor.align_size_within_rect(size, inner)
+    }
+
+    /// Returns the projection of the minimap in `rect` around `center`, for a map at `zoom`. The
+    /// minimap is not zoomed out below `min_zoom`, and is always north-up.
+    pub(crate) fn projection(
+        &self,
+        rect: Rect,
+        center: GeoPos,
+        zoom: f64,
+        min_zoom: f64,
+    ) -> MapProjection {
+        MapProjection::new((zoom + self.zoom_offset).max(min_zoom), center, rect)
+    }
+}
+
+/// Returns the corners of the view of `map`, which covers `map_rect`, on the minimap.
+pub(crate) fn view_outline(
+    map: &MapProjection,
+    map_rect: Rect,
+    minimap: &MapProjection,
+) -> Vec<Pos2> {
+    let corners = [
+        map_rect.left_top(),
+        map_rect.right_top(),
+        map_rect.right_bottom(),
+        map_rect.left_bottom(),
+    ]
+    .map(|corner| map.unproject(corner));
+    minimap.project_path(&corners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::pos2;
+
+    #[test]
+    fn minimap_shows_view_outline() {
+        let map_rect = Rect::from_min_size(pos2(0.0, 0.0), vec2(800.0, 600.0));
+        let minimap = Minimap::default();
+        let rect = minimap.rect(map_rect);
+        assert_eq!(
+            rect,
+            Rect::from_min_max(pos2(630.0, 470.0), pos2(790.0, 590.0))
+        );
+
+        // A map too small for the minimap gets a smaller one.
+        let small = Rect::from_min_size(pos2(0.0, 0.0), vec2(100.0, 100.0));
+        assert_eq!(minimap.rect(small).size(), vec2(80.0, 80.0));
+
+        // The outline of the view is four zoom levels smaller, around the center of the minimap.
+        let center = GeoPos::from((24.9, 60.2));
+        let map = MapProjection::new(10.0, center, map_rect);
+        let projection = minimap.projection(rect, center, 10.0, 0.0);
+        let outline = view_outline(&map, map_rect, &projection);
+        let outline_rect = Rect::from_points(&outline);
+        assert!((outline_rect.center() - rect.center()).length() < 1e-3);
+        assert!((outline_rect.width() - 800.0 / 16.0).abs() < 1e-3);
+        assert!((outline_rect.height() - 600.0 / 16.0).abs() < 1e-3);
+
+        // The minimap is not zoomed out past the lowest zoom level.
+        assert_eq!(minimap.projection(rect, center, 2.0, 0.0).zoom, 0.0);
+    }
+}
@@ -0,0 +1,397 @@
+//! Downloading regions into the disk cache for offline use.
+//!
+//! The tiles of a region are [pinned](DiskCache::pin) in the cache, so that they are not evicted
+//! when the cache has a maximum size. [`release`] lets them be evicted again.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use egui_map_view::{
+//!     Map,
+//!     cache::DiskCache,
+//!     config::OpenStreetMapConfig,
+//!     offline,
+//!     projection::GeoBounds,
+//! };
+//!
+//! let cache = DiskCache::new("/tmp/egui-map-view-tiles");
+//! let helsinki = GeoBounds::new((24.8, 60.1).into(), (25.1, 60.3).into());
+//! let seeding = offline::seed(
+//!     OpenStreetMapConfig::default(),
+//!     cache.clone(),
+//!     helsinki,
+//!     10..=14,
+//!     None,
+//! );
+//!
+//! // Later, for example every frame:
+//! let progress = seeding.progress();
+//! println!("{} / {} tiles", progress.completed(), progress.total);
+//!
+//! // Once the region has been downloaded, the map can run without a network connection.
+//! let map = Map::new(OpenStreetMapConfig::default())
+//!     .with_disk_cache(cache)
+//!     .with_offline(true);
+//! ```
+
+use std::io;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use log::{debug, warn};
+
+use crate::cache::DiskCache;
+use crate::config::MapConfig;
+use crate::download::DownloadPool;
+use crate::http::HttpClient;
+use crate::projection::GeoBounds;
+use crate::{TileId, fetch_tile, lat_to_y, lon_to_x, tile_host};
+
+/// The largest latitude that can be shown on a Web Mercator map.
+const MAX_LATITUDE: f64 = 85.051_128_779_806_59;
+
+/// The number of threads that download tiles while seeding. The downloads go through the
+/// [global download pool](DownloadPool::global), so they share its per-host limit with the maps.
+const SEED_THREADS: usize = 2;
+
+/// Returns the tiles at the given zoom level that cover the bounding box.
+pub fn tiles_in_bounds(bounds: GeoBounds, zoom: u8) -> impl Iterator<Item = TileId> {
+    let (xs, ys) = tile_ranges(bounds, zoom);
+    xs.into_iter()
+        .flatten()
+        .flat_map(move |x| ys.clone().map(move |y| TileId { z: zoom, x, y }))
+}
+
+/// Returns the number of tiles that cover the bounding box at the given zoom levels.
+#[must_use]
+pub fn count_tiles(bounds: GeoBounds, zoom: RangeInclusive<u8>) -> u64 {
+    let len = |range: &RangeInclusive<u32>| u64::from(*range.end()) - u64::from(*range.start()) + 1;
+    zoom.map(|zoom| {
+        let (xs, ys) = tile_ranges(bounds, zoom);
+        xs.iter().map(len).sum::<u64>() * len(&ys)
+    })
+    .sum()
+}
+
+/// Returns the ranges of tile x and y indices that cover the bounding box. A box that crosses the
+/// antimeridian is split into two x ranges.
+fn tile_ranges(bounds: GeoBounds, zoom: u8) -> (Vec<RangeInclusive<u32>>, RangeInclusive<u32>) {
+    let max_index = 2.0_f64.powi(i32::from(zoom)) - 1.0;
+    let index = |v: f64| v.floor().clamp(0.0, max_index) as u32;
+
+    let z = f64::from(zoom);
+    let west = index(lon_to_x(bounds.min.lon, z));
+    let east = index(lon_to_x(bounds.max.lon, z));
+    let xs = if !bounds.crosses_antimeridian() {
+        vec![west..=east]
+    } else if east >= west {
+        // At low zoom levels the two halves overlap and cover the whole world.
+        vec![0..=max_index as u32]
+    } else {
+        vec![west..=max_index as u32, 0..=east]
+    };
+    // Tile y indices grow towards the south.
+    let ys = index(lat_to_y(bounds.max.lat.min(MAX_LATITUDE), z))
+        ..=index(lat_to_y(bounds.min.lat.max(-MAX_LATITUDE), z));
+    (xs, ys)
+}
+
+/// The progress of a [`seed`] operation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SeedProgress {
+    /// The number of tiles in the region.
+    pub total: u64,
+
+    /// The number of tiles that were downloaded.
+    pub downloaded: u64,
+
+    /// The number of tiles that were already fresh in the disk cache.
+    pub cached: u64,
+
+    /// The number of tiles that could not be downloaded, or that the server does not allow to be
+    /// stored.
+    pub failed: u64,
+}
+
+impl SeedProgress {
+    /// Returns the number of tiles that have been processed.
+    #[must_use]
+    pub fn completed(&self) -> u64 {
+        self.downloaded + self.cached + self.failed
+    }
+
+    /// Returns the completed share of the tiles, from 0.0 to 1.0.
+    #[must_use]
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.completed() as f32 / self.total as f32
+        }
+    }
+}
+
+/// A handle to a running [`seed`] operation.
+///
+/// Dropping the handle does not stop the operation, use [`SeedHandle::cancel`] for that.
+pub struct SeedHandle {
+    state: Arc<SeedState>,
+}
+
+impl SeedHandle {
+    /// Returns the current progress.
+    #[must_use]
+    pub fn progress(&self) -> SeedProgress {
+        SeedProgress {
+            total: self.state.total,
+            downloaded: self.state.downloaded.load(Ordering::Relaxed),
+            cached: self.state.cached.load(Ordering::Relaxed),
+            failed: self.state.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Stops the operation after the tiles that are currently being downloaded.
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the operation was cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` once all download threads have stopped, either because every tile was
+    /// processed or because the operation was cancelled.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.state.running.load(Ordering::Acquire) == 0
+    }
+}
+
+/// Bookkeeping shared between a [`SeedHandle`] and its download threads.
+struct SeedState {
+    total: u64,
+    downloaded: AtomicU64,
+    cached: AtomicU64,
+    failed: AtomicU64,
+    cancelled: AtomicBool,
+    running: AtomicUsize,
+}
+
+/// Downloads every tile that covers the bounding box at the given zoom levels into the disk
+/// cache, in the background.
+///
+/// Tiles that are already fresh in the cache are skipped. All the tiles of the region are pinned,
+/// so they count towards the maximum size of the cache but are never evicted. Zoom levels outside
+/// the limits of the provider are ignored.
+///
+/// The tiles are downloaded with `http_client`, or with the client of the configuration if it is
+/// `None`, like a map with [`Map::with_http_client`](crate::Map::with_http_client) does. They
+/// are downloaded through the [global download pool](DownloadPool::global) after the tiles of the
+/// maps that use it. Note that many tile servers, including the OpenStreetMap one, do not allow
+/// downloading large regions, so check the usage policy of the provider first.
+pub fn seed(
+    config: impl MapConfig + Send + Sync + 'static,
+    cache: DiskCache,
+    bounds: GeoBounds,
+    zoom: RangeInclusive<u8>,
+    http_client: Option<HttpClient>,
+) -> SeedHandle {
+    let zoom = (*zoom.start()).max(config.min_zoom())..=(*zoom.end()).min(config.max_zoom());
+    let state = Arc::new(SeedState {
+        total: if zoom.is_empty() {
+            0
+        } else {
+            count_tiles(bounds, zoom.clone())
+        },
+        downloaded: AtomicU64::new(0),
+        cached: AtomicU64::new(0),
+        failed: AtomicU64::new(0),
+        cancelled: AtomicBool::new(false),
+        running: AtomicUsize::new(SEED_THREADS),
+    });
+
+    let tiles: Box<dyn Iterator<Item = TileId> + Send> =
+        Box::new(zoom.flat_map(move |zoom| tiles_in_bounds(bounds, zoom)));
+    let tiles = Arc::new(Mutex::new(tiles));
+    let http_client = http_client
+        .or_else(|| config.http_client())
+        .unwrap_or_default();
+    let config = Arc::new(config);
+    let provider = config.cache_key();
+    let pool = DownloadPool::global();
+
+    for i in 0..SEED_THREADS {
+        let thread_state = state.clone();
+        let tiles = tiles.clone();
+        let config = config.clone();
+        let http_client = http_client.clone();
+        let pool = pool.clone();
+        let cache = (cache.clone(), provider.clone());
+        let spawned = std::thread::Builder::new()
+            .name(format!("seed_tiles_{i}"))
+            .spawn(move || {
+                let state = thread_state;
+                while !state.cancelled.load(Ordering::Relaxed) {
+                    let next = tiles.lock().unwrap_or_else(PoisonError::into_inner).next();
+                    let Some(tile_id) = next else {
+                        break;
+                    };
+                    seed_tile(
+                        config.as_ref(),
+                        &pool,
+                        &http_client,
+                        &cache,
+                        tile_id,
+                        &state,
+                    );
+                }
+                state.running.fetch_sub(1, Ordering::Release);
+            });
+        if let Err(e) = spawned {
+            warn!("Unable to start tile seeding thread: {e}");
+            state.running.fetch_sub(1, Ordering::Release);
+        }
+    }
+
+    SeedHandle { state }
+}
+
+/// Lets the tiles of a region that was downloaded with [`seed`] be evicted from the disk cache
+/// again. The tiles stay in the cache until they are evicted.
+pub fn release(
+    config: &dyn MapConfig,
+    cache: &DiskCache,
+    bounds: GeoBounds,
+    zoom: RangeInclusive<u8>,
+) -> io::Result<()> {
+    let provider = config.cache_key();
+    for tile_id in zoom.flat_map(|zoom| tiles_in_bounds(bounds, zoom)) {
+        cache.unpin(&provider, &tile_id)?;
+    }
+    Ok(())
+}
+
+/// Downloads a single tile into the disk cache, unless it is already fresh there, and pins it once
+/// it is in the cache.
+fn seed_tile(
+    config: &dyn MapConfig,
+    pool: &DownloadPool,
+    http_client: &HttpClient,
+    disk_cache: &(DiskCache, String),
+    tile_id: TileId,
+    state: &SeedState,
+) {
+    let (cache, provider) = disk_cache;
+    let pin = || {
+        if let Err(e) = cache.pin(provider, &tile_id) {
+            warn!("Unable to pin seeded tile {tile_id:?}: {e}");
+        }
+    };
+    if cache
+        .get(provider, &tile_id)
+        .is_some_and(|cached| cached.meta.is_fresh(SystemTime::now()))
+    {
+        pin();
+        state.cached.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+
+    let url = config.tile_url(&tile_id);
+    let download = {
+        let http_client = http_client.clone();
+        let disk_cache = disk_cache.clone();
+        let url = url.clone();
+        // Seeded tiles are downloaded after the tiles that maps are waiting for.
+        pool.spawn(tile_host(&url), f32::MAX, move || {
+            match fetch_tile(&http_client, &url, tile_id, Some(&disk_cache), false) {
+                Ok(tile) if tile.stale => Err(Arc::new(eyre::eyre!(
+                    "The tile server could not be reached"
+                ))),
+                Ok(tile) => Ok(tile.image),
+                Err(e) => Err(Arc::new(e.into())),
+            }
+        })
+    };
+    match download.wait() {
+        // The server may not allow the tile to be stored, in which case it is not there offline.
+        Ok(_) if cache.get(provider, &tile_id).is_none() => {
+            warn!("Unable to seed tile from {url}: the server does not allow storing it");
+            state.failed.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(_) => {
+            pin();
+            debug!("Seeded tile {tile_id:?}");
+            state.downloaded.fetch_add(1, Ordering::Relaxed);
+        }
+        Err(e) => {
+            warn!("Unable to seed tile from {url}: {e}");
+            state.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_in_bounds_covers_region() {
+        // The whole world is a single tile at zoom level 0 and four tiles at zoom level 1.
+        let world = GeoBounds::new((-180.0, -90.0).into(), (180.0, 90.0).into());
+        assert_eq!(
+            tiles_in_bounds(world, 0).collect::<Vec<_>>(),
+            vec![TileId { z: 0, x: 0, y: 0 }]
+        );
+        assert_eq!(tiles_in_bounds(world, 1).count(), 4);
+        assert_eq!(count_tiles(world, 0..=2), 1 + 4 + 16);
+
+        // Helsinki at zoom level 10 is covered by tiles around x = 582, y = 296.
+        let helsinki = GeoBounds::new((24.9, 60.15).into(), (24.95, 60.18).into());
+        let tiles: Vec<_> = tiles_in_bounds(helsinki, 10).collect();
+        assert_eq!(
+            tiles,
+            vec![TileId {
+                z: 10,
+                x: 582,
+                y: 296
+            }]
+        );
+        assert_eq!(count_tiles(helsinki, 10..=10), 1);
+    }
+
+    #[test]
+    fn tiles_in_bounds_across_antimeridian() {
+        // From Fiji to Samoa, across 180°.
+        let pacific = GeoBounds::from_corners((170.0, -20.0).into(), (-170.0, -10.0).into());
+        let tiles: Vec<_> = tiles_in_bounds(pacific, 3).collect();
+        assert_eq!(
+            tiles,
+            vec![TileId { z: 3, x: 7, y: 4 }, TileId { z: 3, x: 0, y: 4 }]
+        );
+        assert_eq!(count_tiles(pacific, 3..=3), 2);
+
+        // The two halves cover the whole world at zoom level 0.
+        assert_eq!(
+            tiles_in_bounds(pacific, 0).collect::<Vec<_>>(),
+            vec![TileId { z: 0, x: 0, y: 0 }]
+        );
+        assert_eq!(count_tiles(pacific, 0..=3), 1 + 2 + 2 + 2);
+    }
+
+    #[test]
+    fn seed_progress_fraction() {
+        let progress = SeedProgress {
+            total: 4,
+            downloaded: 1,
+            cached: 1,
+            failed: 0,
+        };
+        assert_eq!(progress.completed(), 2);
+        assert!((progress.fraction() - 0.5).abs() < f32::EPSILON);
+        assert!((SeedProgress::default().fraction() - 1.0).abs() < f32::EPSILON);
+    }
+}
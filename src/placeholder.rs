@@ -0,0 +1,100 @@
+//! Drawing of tiles that are still loading or failed to load.
+//!
+//! By default such tiles are drawn with colours from the current egui theme. A custom look can
+//! be set with [`Map::with_placeholder`](crate::Map::with_placeholder).
+//!
+//! # Example
+//!
+//! ```
+//! use egui_map_view::{Map, config::OpenStreetMapConfig, placeholder::TileStatus};
+//!
+//! let map = Map::new(OpenStreetMapConfig::default()).with_placeholder(|painter, placeholder| {
+//!     match placeholder.status {
+//!         // Leave loading tiles empty.
+//!         TileStatus::Loading => {}
+//!         _ => egui_map_view::placeholder::draw_default(painter, placeholder),
+//!     }
+//! });
+//! ```
+
+use std::time::Instant;
+
+use egui::emath::Rot2;
+use egui::{Align2, FontId, Painter, Rect, Shape, Visuals};
+
+use crate::TileId;
+
+/// Why a tile is drawn as a placeholder.
+#[derive(Debug)]
+pub enum TileStatus<'a> {
+    /// The tile is queued for download or being downloaded.
+    Loading,
+
+    /// The tile failed to load.
+    Failed {
+        /// The reason of the failure.
+        error: &'a eyre::Report,
+
+        /// When the tile is loaded again, if it is retried.
+        retry_at: Option<Instant>,
+    },
+
+    /// The tile state is unknown.
+    Unknown,
+}
+
+/// A tile that is drawn as a placeholder.
+pub struct Placeholder<'a> {
+    /// The tile.
+    pub tile_id: TileId,
+
+    /// Where the tile is on the screen, before it is rotated by [`rotation`](Self::rotation).
+    pub rect: Rect,
+
+    /// The rotation of the tile around the center of `rect` when the map has a bearing.
+    pub rotation: Rot2,
+
+    /// Why the tile is drawn as a placeholder.
+    pub status: TileStatus<'a>,
+
+    /// The visuals of the map widget, for picking colours that match the theme.
+    pub visuals: &'a Visuals,
+}
+
+/// A function that draws placeholder tiles.
+pub type PlaceholderFn = dyn Fn(&Painter, &Placeholder<'_>);
+
+/// Draws a placeholder with the default look: a background and a border in the colours of the
+/// theme, and a symbol that tells whether the tile is loading or has failed.
+pub fn draw_default(painter: &Painter, placeholder: &Placeholder<'_>) {
+    let visuals = placeholder.visuals;
+    let rect = placeholder.rect;
+    let corners = [
+        rect.left_top(),
+        rect.right_top(),
+        rect.right_bottom(),
+        rect.left_bottom(),
+    ]
+    .map(|corner| rect.center() + placeholder.rotation * (corner - rect.center()));
+    painter.add(Shape::convex_polygon(
+        corners.to_vec(),
+        visuals.faint_bg_color,
+        visuals.widgets.noninteractive.bg_stroke,
+    ));
+
+    let (symbol, color) = match placeholder.status {
+        TileStatus::Loading => ("⌛", visuals.weak_text_color()),
+        TileStatus::Failed {
+            retry_at: Some(_), ..
+        } => ("⟳", visuals.warn_fg_color),
+        TileStatus::Failed { retry_at: None, .. } => ("❌", visuals.error_fg_color),
+        TileStatus::Unknown => ("❓", visuals.warn_fg_color),
+    };
+    painter.text(
+        rect.center(),
+        Align2::CENTER_CENTER,
+        symbol,
+        FontId::proportional(rect.height() / 8.0),
+        color,
+    );
+}
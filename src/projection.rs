@@ -1,50 +1,176 @@
 //! Projections handle converting different coordinate systems between other coordinate systems.
 
-use egui::{Pos2, Rect, vec2};
+use egui::emath::Rot2;
+use egui::{Pos2, Rect, Vec2, vec2};
 use serde::{Deserialize, Serialize};
 
-use crate::{TILE_SIZE, lat_to_y, lon_to_x, x_to_lon, y_to_lat};
+use crate::{TILE_SIZE, lat_to_y, lon_to_x, wrap_lon, x_to_lon, y_to_lat};
 
 /// A helper for converting between geographical and screen coordinates.
 pub struct MapProjection {
-    /// The zoom level of the map.
-    pub zoom: u8,
+    /// The zoom level of the map. May be fractional.
+    pub zoom: f64,
     /// The longitude of the center point of the map.
     pub center_lon: f64,
     /// The latitude of the center point of the map.
     pub center_lat: f64,
     /// The screen rectangle where the map is displayed.
     pub widget_rect: Rect,
+    /// The direction the top of the widget points to, in degrees clockwise from north.
+    bearing: f64,
 }
 
 impl MapProjection {
     /// Creates a new `MapProjection`.
-    pub fn new(zoom: u8, center: GeoPos, widget_rect: Rect) -> Self {
+    pub fn new(zoom: f64, center: GeoPos, widget_rect: Rect) -> Self {
         Self {
             zoom,
             center_lon: center.lon,
             center_lat: center.lat,
             widget_rect,
+            bearing: 0.0,
         }
     }
 
+    /// Rotates the view so that the top of the widget points to `bearing`, in degrees clockwise
+    /// from north.
+    #[must_use]
+    pub fn with_bearing(mut self, bearing: f64) -> Self {
+        self.bearing = bearing;
+        self
+    }
+
+    /// Returns the direction the top of the widget points to, in degrees clockwise from north.
+    /// The view is rotated around the center of the widget. Zero is north-up.
+    #[must_use]
+    pub fn bearing(&self) -> f64 {
+        self.bearing
+    }
+
+    /// Creates the north-up projection that fits a bounding box into the widget, leaving
+    /// `padding` points free on every side.
+    #[must_use]
+    pub fn fit_bounds(bounds: GeoBounds, widget_rect: Rect, padding: f32) -> Self {
+        // The size of the bounding box in tiles of zoom level 0, where the world is one tile.
+        let (west, east) = bounds.lon_range();
+        let min_x = lon_to_x(west, 0.0);
+        let max_x = lon_to_x(east, 0.0);
+        let min_y = lat_to_y(bounds.max.lat, 0.0);
+        let max_y = lat_to_y(bounds.min.lat, 0.0);
+
+        let width = f64::from((widget_rect.width() - 2.0 * padding).max(1.0));
+        let height = f64::from((widget_rect.height() - 2.0 * padding).max(1.0));
+        let scale_x = width / ((max_x - min_x) * f64::from(TILE_SIZE));
+        let scale_y = height / ((max_y - min_y) * f64::from(TILE_SIZE));
+        let zoom = scale_x.min(scale_y).log2().clamp(0.0, f64::from(u8::MAX));
+
+        let center_x = (min_x + max_x) / 2.0;
+        let center_y = (min_y + max_y) / 2.0;
+        Self {
+            zoom,
+            center_lon: wrap_lon(x_to_lon(center_x, 0.0)),
+            center_lat: y_to_lat(center_y, 0.0),
+            widget_rect,
+            bearing: 0.0,
+        }
+    }
+
+    /// Returns the zoom level of the tiles that are shown: the whole level nearest to the zoom
+    /// level of the map.
+    #[must_use]
+    pub fn tile_zoom(&self) -> u8 {
+        self.zoom.round().clamp(0.0, f64::from(u8::MAX)) as u8
+    }
+
+    /// Returns the size of a tile on the screen in points. Tiles are scaled when the zoom level
+    /// of the map is between two whole levels.
+    #[must_use]
+    pub fn tile_size(&self) -> f64 {
+        f64::from(TILE_SIZE) * 2.0_f64.powf(self.zoom - f64::from(self.tile_zoom()))
+    }
+
+    /// Returns the rotation from the north-up view to the screen, around the center of the
+    /// widget.
+    #[must_use]
+    pub fn rotation(&self) -> Rot2 {
+        Rot2::from_angle(-self.bearing.to_radians() as f32)
+    }
+
+    /// Returns the size of the north-up area that covers the rotated widget, in points.
+    #[must_use]
+    pub fn view_size(&self) -> Vec2 {
+        let size = self.widget_rect.size();
+        let (sin, cos) = self.bearing.to_radians().sin_cos();
+        let (sin, cos) = (sin.abs() as f32, cos.abs() as f32);
+        vec2(size.x * cos + size.y * sin, size.x * sin + size.y * cos)
+    }
+
     /// Projects a geographical coordinate to a screen coordinate.
+    ///
+    /// The world repeats horizontally, and the position is projected into the copy of the world
+    /// nearest to the center of the map. Use [`MapProjection::project_path`] for the vertices of
+    /// lines and polygons.
     #[must_use]
     pub fn project(&self, geo_pos: GeoPos) -> Pos2 {
+        self.project_lon(geo_pos, self.nearest_copy(geo_pos.lon))
+    }
+
+    /// Projects the vertices of a line or polygon into a single copy of the world.
+    ///
+    /// Each vertex is placed less than half the world away from the previous one, so that a
+    /// feature that crosses the antimeridian is not torn apart, and the feature as a whole is
+    /// projected into the copy of the world nearest to the center of the map.
+    #[must_use]
+    pub fn project_path(&self, points: &[GeoPos]) -> Vec<Pos2> {
+        let Some(first) = points.first() else {
+            return Vec::new();
+        };
+        let mut lon = first.lon;
+        let lons: Vec<f64> = points
+            .iter()
+            .map(|pos| {
+                lon += wrap_lon(pos.lon - lon);
+                lon
+            })
+            .collect();
+        let (min, max) = lons
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), lon| {
+                (min.min(*lon), max.max(*lon))
+            });
+        let shift = self.nearest_copy((min + max) / 2.0);
+        points
+            .iter()
+            .zip(lons)
+            .map(|(pos, lon)| self.project_lon(GeoPos { lon, lat: pos.lat }, shift))
+            .collect()
+    }
+
+    /// Returns the number of degrees to move a longitude by to get into the copy of the world
+    /// nearest to the center of the map.
+    fn nearest_copy(&self, lon: f64) -> f64 {
+        -((lon - self.center_lon) / 360.0).round() * 360.0
+    }
+
+    /// Projects a geographical coordinate with its longitude moved by `shift` degrees, without
+    /// wrapping it.
+    fn project_lon(&self, geo_pos: GeoPos, shift: f64) -> Pos2 {
         let center_x = lon_to_x(self.center_lon, self.zoom);
         let center_y = lat_to_y(self.center_lat, self.zoom);
 
-        let tile_x = lon_to_x(geo_pos.lon, self.zoom);
+        let tile_x = lon_to_x(geo_pos.lon + shift, self.zoom);
         let tile_y = lat_to_y(geo_pos.lat, self.zoom);
 
         let dx = (tile_x - center_x) * f64::from(TILE_SIZE);
         let dy = (tile_y - center_y) * f64::from(TILE_SIZE);
+        let (dx, dy) = rotate(dx, dy, -self.bearing.to_radians());
 
         let widget_center = self.widget_rect.center();
         widget_center + vec2(dx as f32, dy as f32)
     }
 
-    /// Un-projects a screen coordinate to a geographical coordinate.
+    /// Un-projects a screen coordinate to a geographical coordinate. The longitude is wrapped
+    /// into the range from -180 to 180 degrees.
     #[must_use]
     pub fn unproject(&self, screen_pos: Pos2) -> GeoPos {
         let rel_pos = screen_pos - self.widget_rect.min;
@@ -54,16 +180,27 @@ impl MapProjection {
         let center_x = lon_to_x(self.center_lon, self.zoom);
         let center_y = lat_to_y(self.center_lat, self.zoom);
 
-        let target_x = center_x + (f64::from(rel_pos.x) - widget_center_x) / f64::from(TILE_SIZE);
-        let target_y = center_y + (f64::from(rel_pos.y) - widget_center_y) / f64::from(TILE_SIZE);
+        let (dx, dy) = rotate(
+            f64::from(rel_pos.x) - widget_center_x,
+            f64::from(rel_pos.y) - widget_center_y,
+            self.bearing.to_radians(),
+        );
+        let target_x = center_x + dx / f64::from(TILE_SIZE);
+        let target_y = center_y + dy / f64::from(TILE_SIZE);
 
         GeoPos {
-            lon: x_to_lon(target_x, self.zoom),
+            lon: wrap_lon(x_to_lon(target_x, self.zoom)),
             lat: y_to_lat(target_y, self.zoom),
         }
     }
 }
 
+/// Rotates a screen offset clockwise by `angle` radians.
+fn rotate(x: f64, y: f64, angle: f64) -> (f64, f64) {
+    let (sin, cos) = angle.sin_cos();
+    (x * cos - y * sin, x * sin + y * cos)
+}
+
 /// A geographical position.
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct GeoPos {
@@ -90,6 +227,104 @@ impl GeoPos {
     }
 }
 
+/// A geographical bounding box.
+///
+/// A box whose `min.lon` is larger than its `max.lon` crosses the antimeridian, for example from
+/// 170° to -170°. Build one with [`GeoBounds::from_corners`], as [`GeoBounds::new`] always puts
+/// the smaller longitude first.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GeoBounds {
+    /// The south-west corner. Its longitude is the western edge of the box.
+    pub min: GeoPos,
+
+    /// The north-east corner. Its longitude is the eastern edge of the box.
+    pub max: GeoPos,
+}
+
+impl GeoBounds {
+    /// Creates a bounding box from any two opposite corners. The box never crosses the
+    /// antimeridian.
+    #[must_use]
+    pub fn new(a: GeoPos, b: GeoPos) -> Self {
+        Self {
+            min: GeoPos {
+                lon: a.lon.min(b.lon),
+                lat: a.lat.min(b.lat),
+            },
+            max: GeoPos {
+                lon: a.lon.max(b.lon),
+                lat: a.lat.max(b.lat),
+            },
+        }
+    }
+
+    /// Creates a bounding box from its south-west and north-east corners. The box crosses the
+    /// antimeridian if the longitude of the south-west corner is larger than that of the
+    /// north-east corner.
+    #[must_use]
+    pub fn from_corners(south_west: GeoPos, north_east: GeoPos) -> Self {
+        Self {
+            min: GeoPos {
+                lon: south_west.lon,
+                lat: south_west.lat.min(north_east.lat),
+            },
+            max: GeoPos {
+                lon: north_east.lon,
+                lat: south_west.lat.max(north_east.lat),
+            },
+        }
+    }
+
+    /// Returns `true` if the bounding box crosses the antimeridian.
+    #[must_use]
+    pub fn crosses_antimeridian(&self) -> bool {
+        self.min.lon > self.max.lon
+    }
+
+    /// Returns `true` if the position is inside the bounding box or on its edge.
+    #[must_use]
+    pub fn contains(&self, pos: GeoPos) -> bool {
+        let contains_lon = if self.crosses_antimeridian() {
+            pos.lon >= self.min.lon || pos.lon <= self.max.lon
+        } else {
+            (self.min.lon..=self.max.lon).contains(&pos.lon)
+        };
+        contains_lon && (self.min.lat..=self.max.lat).contains(&pos.lat)
+    }
+
+    /// Returns `true` if the bounding boxes overlap or touch.
+    #[must_use]
+    pub fn intersects(&self, other: &GeoBounds) -> bool {
+        let (west, east) = self.lon_range();
+        let (other_west, other_east) = other.lon_range();
+        // Compare with the other box and its copies one world to the west and to the east.
+        let intersects_lon = [-360.0, 0.0, 360.0]
+            .into_iter()
+            .any(|shift| west <= other_east + shift && other_west + shift <= east);
+        intersects_lon && self.min.lat <= other.max.lat && other.min.lat <= self.max.lat
+    }
+
+    /// Returns the center of the bounding box.
+    #[must_use]
+    pub fn center(&self) -> GeoPos {
+        let (west, east) = self.lon_range();
+        GeoPos {
+            lon: wrap_lon((west + east) / 2.0),
+            lat: (self.min.lat + self.max.lat) / 2.0,
+        }
+    }
+
+    /// Returns the western and eastern longitudes, with the eastern one past 180 degrees if the
+    /// box crosses the antimeridian.
+    pub(crate) fn lon_range(&self) -> (f64, f64) {
+        if self.crosses_antimeridian() {
+            (self.min.lon, self.max.lon + 360.0)
+        } else {
+            (self.min.lon, self.max.lon)
+        }
+    }
+}
+
 impl From<(f64, f64)> for GeoPos {
     fn from((lon, lat): (f64, f64)) -> Self {
         Self { lon, lat }
@@ -135,12 +370,105 @@ mod tests {
 
     fn create_projection() -> MapProjection {
         MapProjection::new(
-            10,
+            10.0,
             GeoPos::from((24.93545, 60.16952)), // Helsinki
             Rect::from_min_size(pos2(100.0, 200.0), vec2(800.0, 600.0)),
         )
     }
 
+    #[test]
+    fn geo_bounds_normalizes_corners() {
+        let bounds = GeoBounds::new((25.0, 61.0).into(), (24.0, 60.0).into());
+        assert_eq!(bounds.min, GeoPos::from((24.0, 60.0)));
+        assert_eq!(bounds.max, GeoPos::from((25.0, 61.0)));
+        assert_eq!(bounds.center(), GeoPos::from((24.5, 60.5)));
+        assert!(bounds.contains((24.93545, 60.16952).into()));
+        assert!(!bounds.contains((23.0, 60.5).into()));
+        assert!(bounds.intersects(&GeoBounds::new((24.5, 59.0).into(), (26.0, 60.5).into())));
+        assert!(!bounds.intersects(&GeoBounds::new((25.5, 60.0).into(), (26.0, 61.0).into())));
+    }
+
+    #[test]
+    fn geo_bounds_across_antimeridian() {
+        // Fiji, from 170° east to 170° west.
+        let fiji = GeoBounds::from_corners((170.0, -12.0).into(), (-170.0, -22.0).into());
+        assert!(fiji.crosses_antimeridian());
+        assert_eq!(fiji.min, GeoPos::from((170.0, -22.0)));
+        assert_eq!(fiji.max, GeoPos::from((-170.0, -12.0)));
+        assert_eq!(fiji.center(), GeoPos::from((-180.0, -17.0)));
+
+        assert!(fiji.contains((178.4, -18.1).into()));
+        assert!(fiji.contains((-175.0, -15.0).into()));
+        assert!(!fiji.contains((0.0, -15.0).into()));
+        assert!(!fiji.contains((178.4, 10.0).into()));
+
+        // Boxes on either side of the antimeridian, and one that crosses it too.
+        assert!(fiji.intersects(&GeoBounds::new(
+            (175.0, -20.0).into(),
+            (180.0, -10.0).into()
+        )));
+        assert!(fiji.intersects(&GeoBounds::new(
+            (-180.0, -20.0).into(),
+            (-175.0, -10.0).into()
+        )));
+        assert!(GeoBounds::new((-180.0, -20.0).into(), (-175.0, -10.0).into()).intersects(&fiji));
+        let tonga = GeoBounds::from_corners((179.0, -24.0).into(), (-173.0, -15.0).into());
+        assert!(fiji.intersects(&tonga));
+        assert!(!fiji.intersects(&GeoBounds::new(
+            (-160.0, -20.0).into(),
+            (160.0, -10.0).into()
+        )));
+        assert!(!fiji.intersects(&GeoBounds::new((0.0, -20.0).into(), (10.0, -10.0).into())));
+    }
+
+    #[test]
+    fn fit_bounds_shows_whole_bounding_box() {
+        let widget_rect = Rect::from_min_size(pos2(0.0, 0.0), vec2(800.0, 600.0));
+        let bounds = GeoBounds::new((20.5, 59.7).into(), (31.6, 70.1).into());
+        let projection = MapProjection::fit_bounds(bounds, widget_rect, 20.0);
+
+        let south_west = projection.project(bounds.min);
+        let north_east = projection.project(bounds.max);
+        let padded = widget_rect.shrink(20.0).expand(0.01);
+        assert!(padded.contains(south_west));
+        assert!(padded.contains(north_east));
+
+        // The bounding box is taller than wide, so it touches the top and the bottom.
+        assert!((south_west.y - 580.0).abs() < 0.01);
+        assert!((north_east.y - 20.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn fit_bounds_across_antimeridian() {
+        let widget_rect = Rect::from_min_size(pos2(0.0, 0.0), vec2(800.0, 600.0));
+        let fiji = GeoBounds::from_corners((170.0, -22.0).into(), (-170.0, -12.0).into());
+        let projection = MapProjection::fit_bounds(fiji, widget_rect, 20.0);
+
+        // The box is 20 degrees wide, not 340 degrees, and is centered on the antimeridian.
+        assert!(projection.zoom.is_finite());
+        assert!(projection.zoom > 4.0);
+        assert!((projection.center_lon.abs() - 180.0).abs() < EPSILON);
+
+        let padded = widget_rect.shrink(20.0).expand(0.01);
+        assert!(padded.contains(projection.project(fiji.min)));
+        assert!(padded.contains(projection.project(fiji.max)));
+    }
+
+    #[test]
+    fn tile_zoom_is_nearest_whole_level() {
+        let mut projection = create_projection();
+        assert_eq!(projection.tile_zoom(), 10);
+        assert!((projection.tile_size() - 256.0).abs() < EPSILON);
+
+        projection.zoom = 10.4;
+        assert_eq!(projection.tile_zoom(), 10);
+        assert!((projection.tile_size() - 256.0 * 2.0_f64.powf(0.4)).abs() < EPSILON);
+
+        projection.zoom = 10.6;
+        assert_eq!(projection.tile_zoom(), 11);
+        assert!((projection.tile_size() - 256.0 * 2.0_f64.powf(-0.4)).abs() < EPSILON);
+    }
+
     #[test]
     fn project_center() {
         let projection = create_projection();
@@ -170,6 +498,71 @@ mod tests {
         assert!((geo_pos_in.lat - geo_pos_out.lat).abs() < EPSILON);
     }
 
+    #[test]
+    fn bearing_rotates_view() {
+        let projection = create_projection().with_bearing(90.0);
+        let center = projection.widget_rect.center();
+
+        // With east up, a point east of the center is above it.
+        let east = GeoPos::from((projection.center_lon + 0.01, projection.center_lat));
+        let screen_pos = projection.project(east);
+        assert!((screen_pos.x - center.x).abs() < 1e-3);
+        assert!(screen_pos.y < center.y);
+
+        let geo_pos = projection.unproject(screen_pos);
+        assert!((geo_pos.lon - east.lon).abs() < 1e-6); // f32 precision
+        assert!((geo_pos.lat - east.lat).abs() < 1e-6);
+
+        // The rotated widget is covered by an area as tall as the widget is wide.
+        let view_size = projection.view_size();
+        assert!((view_size.x - 600.0).abs() < 1e-3);
+        assert!((view_size.y - 800.0).abs() < 1e-3);
+        assert!(((projection.rotation() * vec2(1.0, 0.0)) - vec2(0.0, -1.0)).length() < 1e-6);
+    }
+
+    #[test]
+    fn world_wraps_across_antimeridian() {
+        let projection = MapProjection::new(
+            4.0,
+            GeoPos::from((179.0, 0.0)),
+            Rect::from_min_size(pos2(0.0, 0.0), vec2(800.0, 600.0)),
+        );
+        let center = projection.widget_rect.center();
+
+        // Just across the antimeridian is just to the right of the center.
+        let screen_pos = projection.project(GeoPos::from((-179.0, 0.0)));
+        assert!(screen_pos.x > center.x);
+        assert!(screen_pos.x - center.x < 50.0);
+
+        let geo_pos = projection.unproject(screen_pos);
+        assert!((geo_pos.lon + 179.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn paths_stay_in_one_world_copy() {
+        let projection = MapProjection::new(
+            2.0,
+            GeoPos::from((150.0, 0.0)),
+            Rect::from_min_size(pos2(0.0, 0.0), vec2(800.0, 600.0)),
+        );
+        let world_width = 4.0 * TILE_SIZE as f32;
+
+        // An Atlantic route seen from the Pacific is on the far side of the world. Its vertices
+        // are in different copies of the world one by one, but the route is drawn in one piece.
+        let route = [GeoPos::from((-70.0, 40.0)), GeoPos::from((0.0, 50.0))];
+        let apart = projection.project(route[1]).x - projection.project(route[0]).x;
+        assert!(apart < 0.0);
+        let path = projection.project_path(&route);
+        assert!((path[1].x - path[0].x - world_width * 70.0 / 360.0).abs() < 1e-2);
+        assert!(path[0].x > projection.widget_rect.center().x);
+
+        // A line across the antimeridian takes the short way.
+        let path =
+            projection.project_path(&[GeoPos::from((170.0, 0.0)), GeoPos::from((-170.0, 0.0))]);
+        assert!((path[1].x - path[0].x - world_width * 20.0 / 360.0).abs() < 1e-2);
+        assert!(projection.project_path(&[]).is_empty());
+    }
+
     #[test]
     fn unproject_project_roundtrip() {
         let projection = create_projection();
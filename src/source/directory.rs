@@ -0,0 +1,218 @@
+//! Tiles from a `{z}/{x}/{y}.png` directory tree, as produced for example by `gdal2tiles` or the
+//! "Generate XYZ tiles" tool of QGIS.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use egui_map_view::{Map, source::directory::DirectoryConfig};
+//!
+//! // gdal2tiles numbers the rows from the south unless it is run with `--xyz`.
+//! let config = DirectoryConfig::new("/data/tiles")
+//!     .with_pattern("{z}/{x}/{-y}.{ext}")
+//!     .with_extension("jpg")
+//!     .with_max_zoom(16);
+//! let map = Map::new(config);
+//! ```
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::config::MapConfig;
+use crate::source::{SourceMetadata, TileSource};
+use crate::{MapError, TileId};
+
+/// The default layout of a tile directory.
+const DEFAULT_PATTERN: &str = "{z}/{x}/{y}.{ext}";
+
+/// Reads tiles from files in a directory tree.
+///
+/// The path of a tile relative to the root directory is given by a pattern with the placeholders
+/// `{z}`, `{x}`, `{y}` and `{ext}`. `{-y}` is the row numbered from the south, as in the TMS
+/// scheme.
+#[derive(Clone, Debug)]
+pub struct DirectoryTiles {
+    root: PathBuf,
+    pattern: String,
+    extension: String,
+}
+
+impl DirectoryTiles {
+    /// Reads tiles from `{root}/{z}/{x}/{y}.png`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            pattern: DEFAULT_PATTERN.to_string(),
+            extension: "png".to_string(),
+        }
+    }
+
+    /// Returns the path of a tile file.
+    #[must_use]
+    pub fn tile_path(&self, tile: &TileId) -> PathBuf {
+        let tms_y = (1_i64 << tile.z.min(32)) - 1 - i64::from(tile.y);
+        let relative = self
+            .pattern
+            .replace("{z}", &tile.z.to_string())
+            .replace("{x}", &tile.x.to_string())
+            .replace("{-y}", &tms_y.to_string())
+            .replace("{y}", &tile.y.to_string())
+            .replace("{ext}", &self.extension);
+        self.root.join(relative)
+    }
+}
+
+impl TileSource for DirectoryTiles {
+    fn tile_bytes(&self, tile: &TileId) -> Result<Option<Vec<u8>>, MapError> {
+        match std::fs::read(self.tile_path(tile)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(MapError::TileSourceError(e.to_string())),
+        }
+    }
+}
+
+/// Configuration for a map that shows the tiles of a directory tree on the local filesystem.
+///
+/// # Example
+///
+/// ```
+/// use egui_map_view::source::directory::DirectoryConfig;
+/// let config = DirectoryConfig::new("/data/tiles").with_attribution("© Me");
+/// ```
+pub struct DirectoryConfig {
+    source: Arc<DirectoryTiles>,
+    metadata: SourceMetadata,
+}
+
+impl DirectoryConfig {
+    /// Creates a configuration that reads tiles from `{root}/{z}/{x}/{y}.png`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            source: Arc::new(DirectoryTiles::new(root)),
+            metadata: SourceMetadata::default(),
+        }
+    }
+
+    /// Sets the path of the tile files relative to the root directory. See [`DirectoryTiles`]
+    /// for the placeholders.
+    #[must_use]
+    pub fn with_pattern(mut self, pattern: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.source).pattern = pattern.into();
+        self
+    }
+
+    /// Sets the file extension that replaces the `{ext}` placeholder. Defaults to `png`.
+    #[must_use]
+    pub fn with_extension(mut self, extension: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.source).extension = extension.into();
+        self
+    }
+
+    /// Sets the attribution text displayed on the map.
+    #[must_use]
+    pub fn with_attribution(mut self, attribution: impl Into<String>) -> Self {
+        self.metadata.attribution = Some(attribution.into());
+        self
+    }
+
+    /// Sets the default center (longitude, latitude) and zoom level of the map.
+    #[must_use]
+    pub fn with_default_view(mut self, center: (f64, f64), zoom: u8) -> Self {
+        self.metadata.default_center = center;
+        self.metadata.default_zoom = zoom;
+        self
+    }
+
+    /// Sets the minimum zoom level.
+    #[must_use]
+    pub fn with_min_zoom(mut self, min_zoom: u8) -> Self {
+        self.metadata.min_zoom = min_zoom;
+        self
+    }
+
+    /// Sets the maximum zoom level.
+    #[must_use]
+    pub fn with_max_zoom(mut self, max_zoom: u8) -> Self {
+        self.metadata.max_zoom = max_zoom;
+        self
+    }
+}
+
+impl MapConfig for DirectoryConfig {
+    fn tile_url(&self, tile: &TileId) -> String {
+        format!("file://{}", self.source.tile_path(tile).display())
+    }
+
+    fn tile_source(&self) -> Option<Arc<dyn TileSource>> {
+        Some(self.source.clone())
+    }
+
+    fn attribution(&self) -> Option<&String> {
+        self.metadata.attribution.as_ref()
+    }
+
+    fn attribution_url(&self) -> Option<&String> {
+        None
+    }
+
+    fn default_center(&self) -> (f64, f64) {
+        self.metadata.default_center
+    }
+
+    fn default_zoom(&self) -> u8 {
+        self.metadata.default_zoom
+    }
+
+    fn min_zoom(&self) -> u8 {
+        self.metadata.min_zoom
+    }
+
+    fn max_zoom(&self) -> u8 {
+        self.metadata.max_zoom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directory_tiles_paths() {
+        let tiles = DirectoryTiles::new("/data/tiles");
+        let tile = TileId { z: 2, x: 1, y: 0 };
+        assert_eq!(
+            tiles.tile_path(&tile),
+            PathBuf::from("/data/tiles/2/1/0.png")
+        );
+
+        let config = DirectoryConfig::new("/data/tiles")
+            .with_pattern("{z}_{x}_{-y}.{ext}")
+            .with_extension("jpg");
+        assert_eq!(
+            config.source.tile_path(&tile),
+            PathBuf::from("/data/tiles/2_1_3.jpg")
+        );
+        assert_eq!(config.tile_url(&tile), "file:///data/tiles/2_1_3.jpg");
+    }
+
+    #[test]
+    fn directory_tiles_reads_files() {
+        let root =
+            std::env::temp_dir().join(format!("egui-map-view-test-{}-dir", std::process::id()));
+        std::fs::create_dir_all(root.join("1/0")).unwrap();
+        std::fs::write(root.join("1/0/1.png"), b"tile").unwrap();
+
+        let source = DirectoryConfig::new(&root).tile_source().unwrap();
+        assert_eq!(
+            source.tile_bytes(&TileId { z: 1, x: 0, y: 1 }).unwrap(),
+            Some(b"tile".to_vec())
+        );
+        assert_eq!(
+            source.tile_bytes(&TileId { z: 1, x: 1, y: 1 }).unwrap(),
+            None
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
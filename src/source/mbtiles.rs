@@ -0,0 +1,250 @@
+//! Raster tiles from an [MBTiles](https://github.com/mapbox/mbtiles-spec) SQLite file.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use egui_map_view::{Map, source::mbtiles::MbTilesConfig};
+//!
+//! let config = MbTilesConfig::open("tiles.mbtiles").expect("Unable to open tile file");
+//! let map = Map::new(config);
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+
+use crate::config::MapConfig;
+use crate::source::{SourceMetadata, TileSource};
+use crate::{MapError, TileId};
+
+/// A connection to an MBTiles file.
+pub struct MbTiles {
+    connection: Mutex<Connection>,
+}
+
+impl MbTiles {
+    /// Opens an MBTiles file for reading.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MapError> {
+        let connection = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(source_error)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Returns a value from the `metadata` table of the file.
+    pub fn metadata(&self, name: &str) -> Result<Option<String>, MapError> {
+        self.connection()
+            .query_row(
+                "SELECT value FROM metadata WHERE name = ?1",
+                [name],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(source_error)
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Reads the display settings from the `metadata` table.
+    fn source_metadata(&self) -> Result<SourceMetadata, MapError> {
+        let mut metadata = SourceMetadata {
+            attribution: self.metadata("attribution")?,
+            ..SourceMetadata::default()
+        };
+        if let Some(min_zoom) = self.metadata("minzoom")?.and_then(|v| v.parse().ok()) {
+            metadata.min_zoom = min_zoom;
+        }
+        if let Some(max_zoom) = self.metadata("maxzoom")?.and_then(|v| v.parse().ok()) {
+            metadata.max_zoom = max_zoom;
+        }
+
+        // The center is "lon,lat,zoom" and the bounds are "left,bottom,right,top".
+        let numbers = |value: Option<String>| -> Vec<f64> {
+            value
+                .map(|v| v.split(',').filter_map(|n| n.trim().parse().ok()).collect())
+                .unwrap_or_default()
+        };
+        let center = numbers(self.metadata("center")?);
+        let bounds = numbers(self.metadata("bounds")?);
+        if let [lon, lat, rest @ ..] = center.as_slice() {
+            metadata.default_center = (*lon, *lat);
+            if let Some(zoom) = rest.first() {
+                metadata.default_zoom = *zoom as u8;
+            }
+        } else if let [left, bottom, right, top] = bounds.as_slice() {
+            metadata.default_center = ((left + right) / 2.0, (bottom + top) / 2.0);
+        }
+        metadata.default_zoom = metadata
+            .default_zoom
+            .clamp(metadata.min_zoom, metadata.max_zoom.max(metadata.min_zoom));
+        Ok(metadata)
+    }
+}
+
+impl TileSource for MbTiles {
+    fn tile_bytes(&self, tile: &TileId) -> Result<Option<Vec<u8>>, MapError> {
+        // MBTiles uses the TMS scheme, where the rows are numbered from the south.
+        let rows = 1_i64 << tile.z.min(32);
+        let row = rows - 1 - i64::from(tile.y);
+        if row < 0 {
+            return Ok(None);
+        }
+        self.connection()
+            .prepare_cached(
+                "SELECT tile_data FROM tiles \
+                 WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+            )
+            .and_then(|mut statement| {
+                statement
+                    .query_row((tile.z, tile.x, row), |row| row.get(0))
+                    .optional()
+            })
+            .map_err(source_error)
+    }
+}
+
+/// Configuration for a map that shows the raster tiles of an MBTiles file.
+///
+/// The attribution, the zoom limits and the default view are read from the metadata of the file.
+pub struct MbTilesConfig {
+    path: PathBuf,
+    source: Arc<MbTiles>,
+    metadata: SourceMetadata,
+}
+
+impl MbTilesConfig {
+    /// Opens an MBTiles file.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, MapError> {
+        let path = path.into();
+        let source = MbTiles::open(&path)?;
+        let metadata = source.source_metadata()?;
+        Ok(Self {
+            path,
+            source: Arc::new(source),
+            metadata,
+        })
+    }
+
+    /// Sets the minimum zoom level.
+    #[must_use]
+    pub fn with_min_zoom(mut self, min_zoom: u8) -> Self {
+        self.metadata.min_zoom = min_zoom;
+        self
+    }
+
+    /// Sets the maximum zoom level.
+    #[must_use]
+    pub fn with_max_zoom(mut self, max_zoom: u8) -> Self {
+        self.metadata.max_zoom = max_zoom;
+        self
+    }
+}
+
+impl MapConfig for MbTilesConfig {
+    fn tile_url(&self, tile: &TileId) -> String {
+        format!(
+            "mbtiles://{}/{}/{}/{}",
+            self.path.display(),
+            tile.z,
+            tile.x,
+            tile.y
+        )
+    }
+
+    fn tile_source(&self) -> Option<Arc<dyn TileSource>> {
+        Some(self.source.clone())
+    }
+
+    fn attribution(&self) -> Option<&String> {
+        self.metadata.attribution.as_ref()
+    }
+
+    fn attribution_url(&self) -> Option<&String> {
+        None
+    }
+
+    fn default_center(&self) -> (f64, f64) {
+        self.metadata.default_center
+    }
+
+    fn default_zoom(&self) -> u8 {
+        self.metadata.default_zoom
+    }
+
+    fn min_zoom(&self) -> u8 {
+        self.metadata.min_zoom
+    }
+
+    fn max_zoom(&self) -> u8 {
+        self.metadata.max_zoom
+    }
+}
+
+fn source_error(e: rusqlite::Error) -> MapError {
+    MapError::TileSourceError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "egui-map-view-test-{}-{name}.mbtiles",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let connection = Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE metadata (name TEXT, value TEXT);
+                 CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER,
+                                     tile_data BLOB);
+                 INSERT INTO metadata VALUES ('attribution', '© Test'), ('minzoom', '1'),
+                                             ('maxzoom', '12'), ('center', '24.9,60.1,8');
+                 INSERT INTO tiles VALUES (1, 0, 0, x'0102');",
+            )
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn mbtiles_config_reads_metadata() {
+        let path = test_file("metadata");
+        let config = MbTilesConfig::open(&path).unwrap();
+        assert_eq!(config.attribution().map(String::as_str), Some("© Test"));
+        assert_eq!(MapConfig::min_zoom(&config), 1);
+        assert_eq!(MapConfig::max_zoom(&config), 12);
+        assert_eq!(config.default_center(), (24.9, 60.1));
+        assert_eq!(config.default_zoom(), 8);
+        drop(config);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn mbtiles_flips_rows() {
+        let path = test_file("rows");
+        let source = MbTiles::open(&path).unwrap();
+        // The tile in TMS row 0 is the southern tile, y = 1 in the XYZ scheme.
+        assert_eq!(
+            source.tile_bytes(&TileId { z: 1, x: 0, y: 1 }).unwrap(),
+            Some(vec![1, 2])
+        );
+        assert_eq!(
+            source.tile_bytes(&TileId { z: 1, x: 0, y: 0 }).unwrap(),
+            None
+        );
+        drop(source);
+        std::fs::remove_file(path).unwrap();
+    }
+}
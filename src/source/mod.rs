@@ -0,0 +1,51 @@
+//! Tile sources that provide the tile images directly instead of through a tile server URL.
+//!
+//! A [`MapConfig`](crate::config::MapConfig) returns a source from
+//! [`MapConfig::tile_source`](crate::config::MapConfig::tile_source). The map then reads the tiles
+//! from the source in the background, and never downloads or caches them on disk.
+
+use crate::{MapError, TileId};
+
+/// Tile directories on the local filesystem.
+pub mod directory;
+
+/// MBTiles files.
+#[cfg(feature = "mbtiles")]
+pub mod mbtiles;
+
+/// PMTiles archives.
+#[cfg(feature = "pmtiles")]
+pub mod pmtiles;
+
+/// A source of encoded tile images, such as a local tile file.
+///
+/// Tiles are read on background threads, so the source must be `Send` and `Sync`.
+pub trait TileSource: Send + Sync {
+    /// Reads the encoded image bytes of a tile.
+    ///
+    /// Returns `Ok(None)` if the source does not contain the tile. Such tiles are drawn as
+    /// transparent.
+    fn tile_bytes(&self, tile: &TileId) -> Result<Option<Vec<u8>>, MapError>;
+}
+
+/// Display settings of a tile source, read from the metadata of a tile file or set by the user.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SourceMetadata {
+    pub(crate) attribution: Option<String>,
+    pub(crate) min_zoom: u8,
+    pub(crate) max_zoom: u8,
+    pub(crate) default_center: (f64, f64),
+    pub(crate) default_zoom: u8,
+}
+
+impl Default for SourceMetadata {
+    fn default() -> Self {
+        Self {
+            attribution: None,
+            min_zoom: 0,
+            max_zoom: 19,
+            default_center: (0.0, 0.0),
+            default_zoom: 2,
+        }
+    }
+}
@@ -0,0 +1,573 @@
+//! Raster tiles from a [PMTiles](https://github.com/protomaps/PMTiles) version 3 archive.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use egui_map_view::{Map, source::pmtiles::PmTilesConfig};
+//!
+//! let config = PmTilesConfig::open("tiles.pmtiles").expect("Unable to open tile archive");
+//! let map = Map::new(config);
+//! ```
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use flate2::read::GzDecoder;
+
+use crate::config::MapConfig;
+use crate::source::{SourceMetadata, TileSource};
+use crate::{MapError, TileId};
+
+/// The size of the fixed header at the start of an archive.
+const HEADER_LEN: usize = 127;
+
+/// Leaf directories may only be nested this deep.
+const MAX_DIRECTORY_DEPTH: usize = 4;
+
+/// Compression of the directories and tiles of an archive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Compression {
+    Unknown,
+    None,
+    Gzip,
+    Other(u8),
+}
+
+impl From<u8> for Compression {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Unknown,
+            1 => Self::None,
+            2 => Self::Gzip,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl Compression {
+    fn decompress(self, bytes: Vec<u8>) -> Result<Vec<u8>, MapError> {
+        match self {
+            Self::Unknown | Self::None => Ok(bytes),
+            Self::Gzip => {
+                let mut out = Vec::new();
+                GzDecoder::new(bytes.as_slice())
+                    .read_to_end(&mut out)
+                    .map_err(source_error)?;
+                Ok(out)
+            }
+            Self::Other(value) => Err(MapError::TileSourceError(format!(
+                "Unsupported PMTiles compression {value}"
+            ))),
+        }
+    }
+}
+
+/// The fields of the archive header that are needed to read tiles.
+#[derive(Clone, Debug)]
+struct Header {
+    root_offset: u64,
+    root_length: u64,
+    metadata_offset: u64,
+    metadata_length: u64,
+    leaf_offset: u64,
+    data_offset: u64,
+    internal_compression: Compression,
+    tile_compression: Compression,
+    min_zoom: u8,
+    max_zoom: u8,
+    center_zoom: u8,
+    center_lon: f64,
+    center_lat: f64,
+}
+
+impl Header {
+    fn parse(bytes: &[u8; HEADER_LEN]) -> Result<Self, MapError> {
+        if &bytes[0..7] != b"PMTiles" || bytes[7] != 3 {
+            return Err(MapError::TileSourceError(
+                "Not a PMTiles version 3 archive".to_string(),
+            ));
+        }
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap_or([0; 8]));
+        let e7_at = |at: usize| {
+            f64::from(i32::from_le_bytes(
+                bytes[at..at + 4].try_into().unwrap_or([0; 4]),
+            )) / 1e7
+        };
+        Ok(Self {
+            root_offset: u64_at(8),
+            root_length: u64_at(16),
+            metadata_offset: u64_at(24),
+            metadata_length: u64_at(32),
+            leaf_offset: u64_at(40),
+            data_offset: u64_at(56),
+            internal_compression: bytes[97].into(),
+            tile_compression: bytes[98].into(),
+            min_zoom: bytes[100],
+            max_zoom: bytes[101],
+            center_zoom: bytes[118],
+            center_lon: e7_at(119),
+            center_lat: e7_at(123),
+        })
+    }
+}
+
+/// An entry of a directory. Entries with a run length of zero point to leaf directories.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Entry {
+    tile_id: u64,
+    offset: u64,
+    length: u64,
+    run_length: u64,
+}
+
+/// A PMTiles archive.
+pub struct PmTiles {
+    file: Mutex<File>,
+    /// The size of the archive in bytes.
+    len: u64,
+    header: Header,
+    root: Vec<Entry>,
+}
+
+impl PmTiles {
+    /// Opens a PMTiles archive for reading.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MapError> {
+        let mut file = File::open(path).map_err(source_error)?;
+        let len = file.metadata().map_err(source_error)?.len();
+        let mut header = [0; HEADER_LEN];
+        file.read_exact(&mut header).map_err(source_error)?;
+        let header = Header::parse(&header)?;
+
+        let mut archive = Self {
+            file: Mutex::new(file),
+            len,
+            header,
+            root: Vec::new(),
+        };
+        archive.root =
+            archive.read_directory(archive.header.root_offset, archive.header.root_length)?;
+        Ok(archive)
+    }
+
+    /// Returns the JSON metadata of the archive.
+    pub fn metadata(&self) -> Result<serde_json::Value, MapError> {
+        if self.header.metadata_length == 0 {
+            return Ok(serde_json::Value::Null);
+        }
+        let bytes = self.read(self.header.metadata_offset, self.header.metadata_length)?;
+        let bytes = self.header.internal_compression.decompress(bytes)?;
+        serde_json::from_slice(&bytes).map_err(|e| MapError::TileSourceError(e.to_string()))
+    }
+
+    fn read(&self, offset: u64, length: u64) -> Result<Vec<u8>, MapError> {
+        // Check the range before allocating, so that a corrupt archive cannot ask for huge buffers.
+        if offset.checked_add(length).is_none_or(|end| end > self.len) {
+            return Err(MapError::TileSourceError(
+                "PMTiles range is outside the archive".to_string(),
+            ));
+        }
+        let length = usize::try_from(length)
+            .map_err(|e| MapError::TileSourceError(format!("Invalid PMTiles length: {e}")))?;
+        let mut bytes = vec![0; length];
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        file.seek(SeekFrom::Start(offset)).map_err(source_error)?;
+        file.read_exact(&mut bytes).map_err(source_error)?;
+        Ok(bytes)
+    }
+
+    fn read_directory(&self, offset: u64, length: u64) -> Result<Vec<Entry>, MapError> {
+        let bytes = self.read(offset, length)?;
+        let bytes = self.header.internal_compression.decompress(bytes)?;
+        parse_directory(&bytes)
+    }
+
+    /// Reads the display settings from the header and the JSON metadata.
+    fn source_metadata(&self) -> Result<SourceMetadata, MapError> {
+        let attribution = self
+            .metadata()?
+            .get("attribution")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+        let min_zoom = self.header.min_zoom;
+        let max_zoom = self.header.max_zoom.max(min_zoom);
+        Ok(SourceMetadata {
+            attribution,
+            min_zoom,
+            max_zoom,
+            default_center: (self.header.center_lon, self.header.center_lat),
+            default_zoom: self.header.center_zoom.clamp(min_zoom, max_zoom),
+        })
+    }
+}
+
+impl TileSource for PmTiles {
+    fn tile_bytes(&self, tile: &TileId) -> Result<Option<Vec<u8>>, MapError> {
+        if tile.z > 31 || u64::from(tile.x) >> tile.z != 0 || u64::from(tile.y) >> tile.z != 0 {
+            return Ok(None);
+        }
+        let tile_id = zxy_to_tile_id(tile.z, tile.x, tile.y);
+
+        let mut leaf;
+        let mut directory = &self.root;
+        for _ in 0..MAX_DIRECTORY_DEPTH {
+            let Some(entry) = find_entry(directory, tile_id) else {
+                return Ok(None);
+            };
+            if entry.run_length > 0 {
+                let offset = checked_offset(self.header.data_offset, entry.offset)?;
+                let bytes = self.read(offset, entry.length)?;
+                return self.header.tile_compression.decompress(bytes).map(Some);
+            }
+            let offset = checked_offset(self.header.leaf_offset, entry.offset)?;
+            leaf = self.read_directory(offset, entry.length)?;
+            directory = &leaf;
+        }
+        Err(MapError::TileSourceError(
+            "PMTiles leaf directories are nested too deep".to_string(),
+        ))
+    }
+}
+
+/// Configuration for a map that shows the raster tiles of a PMTiles archive.
+///
+/// The attribution, the zoom limits and the default view are read from the archive.
+pub struct PmTilesConfig {
+    path: PathBuf,
+    source: Arc<PmTiles>,
+    metadata: SourceMetadata,
+}
+
+impl PmTilesConfig {
+    /// Opens a PMTiles archive.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, MapError> {
+        let path = path.into();
+        let source = PmTiles::open(&path)?;
+        let metadata = source.source_metadata()?;
+        Ok(Self {
+            path,
+            source: Arc::new(source),
+            metadata,
+        })
+    }
+
+    /// Sets the minimum zoom level.
+    #[must_use]
+    pub fn with_min_zoom(mut self, min_zoom: u8) -> Self {
+        self.metadata.min_zoom = min_zoom;
+        self
+    }
+
+    /// Sets the maximum zoom level.
+    #[must_use]
+    pub fn with_max_zoom(mut self, max_zoom: u8) -> Self {
+        self.metadata.max_zoom = max_zoom;
+        self
+    }
+}
+
+impl MapConfig for PmTilesConfig {
+    fn tile_url(&self, tile: &TileId) -> String {
+        format!(
+            "pmtiles://{}/{}/{}/{}",
+            self.path.display(),
+            tile.z,
+            tile.x,
+            tile.y
+        )
+    }
+
+    fn tile_source(&self) -> Option<Arc<dyn TileSource>> {
+        Some(self.source.clone())
+    }
+
+    fn attribution(&self) -> Option<&String> {
+        self.metadata.attribution.as_ref()
+    }
+
+    fn attribution_url(&self) -> Option<&String> {
+        None
+    }
+
+    fn default_center(&self) -> (f64, f64) {
+        self.metadata.default_center
+    }
+
+    fn default_zoom(&self) -> u8 {
+        self.metadata.default_zoom
+    }
+
+    fn min_zoom(&self) -> u8 {
+        self.metadata.min_zoom
+    }
+
+    fn max_zoom(&self) -> u8 {
+        self.metadata.max_zoom
+    }
+}
+
+/// Converts a tile to its position on the Hilbert curve that orders the tiles of an archive.
+fn zxy_to_tile_id(z: u8, x: u32, y: u32) -> u64 {
+    // The number of tiles on all lower zoom levels.
+    let base = ((1_u64 << (2 * u32::from(z))) - 1) / 3;
+
+    let (mut x, mut y) = (u64::from(x), u64::from(y));
+    let mut d = 0;
+    let mut s = (1_u64 << z) / 2;
+    while s > 0 {
+        let rx = u64::from(x & s > 0);
+        let ry = u64::from(y & s > 0);
+        d += s * s * ((3 * rx) ^ ry);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    base + d
+}
+
+/// Returns the entry that contains the tile, or the leaf directory that may contain it.
+fn find_entry(entries: &[Entry], tile_id: u64) -> Option<Entry> {
+    let index = entries.partition_point(|entry| entry.tile_id <= tile_id);
+    let entry = *entries.get(index.checked_sub(1)?)?;
+    (entry.run_length == 0
+        || entry
+            .tile_id
+            .checked_add(entry.run_length)
+            .is_some_and(|end| tile_id < end))
+    .then_some(entry)
+}
+
+/// Parses a decompressed directory.
+fn parse_directory(mut bytes: &[u8]) -> Result<Vec<Entry>, MapError> {
+    let count = usize::try_from(read_varint(&mut bytes)?)
+        .map_err(|e| MapError::TileSourceError(e.to_string()))?;
+    let mut entries = vec![
+        Entry {
+            tile_id: 0,
+            offset: 0,
+            length: 0,
+            run_length: 0,
+        };
+        count.min(bytes.len())
+    ];
+    if entries.len() != count {
+        return Err(invalid_directory());
+    }
+
+    let mut tile_id = 0;
+    for entry in &mut entries {
+        tile_id = checked_offset(tile_id, read_varint(&mut bytes)?)?;
+        entry.tile_id = tile_id;
+    }
+    for entry in &mut entries {
+        entry.run_length = read_varint(&mut bytes)?;
+        checked_offset(entry.tile_id, entry.run_length)?;
+    }
+    for entry in &mut entries {
+        entry.length = read_varint(&mut bytes)?;
+    }
+    for i in 0..entries.len() {
+        let value = read_varint(&mut bytes)?;
+        entries[i].offset = if value == 0 && i > 0 {
+            // Zero means that the tile directly follows the previous one.
+            checked_offset(entries[i - 1].offset, entries[i - 1].length)?
+        } else {
+            value.checked_sub(1).ok_or_else(invalid_directory)?
+        };
+    }
+    Ok(entries)
+}
+
+/// Reads an unsigned LEB128 variable-length integer.
+fn read_varint(bytes: &mut &[u8]) -> Result<u64, MapError> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or_else(invalid_directory)?;
+        *bytes = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid_directory())
+}
+
+/// Adds two offsets of an archive, failing if a corrupt archive makes them overflow.
+fn checked_offset(a: u64, b: u64) -> Result<u64, MapError> {
+    a.checked_add(b).ok_or_else(invalid_directory)
+}
+
+fn invalid_directory() -> MapError {
+    MapError::TileSourceError("Invalid PMTiles directory".to_string())
+}
+
+fn source_error(e: std::io::Error) -> MapError {
+    MapError::TileSourceError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            out.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    /// Writes an uncompressed archive with the given tiles, all in the root directory.
+    fn test_archive(name: &str, tiles: &[(TileId, &[u8])]) -> PathBuf {
+        let mut tiles: Vec<_> = tiles
+            .iter()
+            .map(|(tile, bytes)| (zxy_to_tile_id(tile.z, tile.x, tile.y), *bytes))
+            .collect();
+        tiles.sort_by_key(|(tile_id, _)| *tile_id);
+
+        let mut directory = Vec::new();
+        write_varint(&mut directory, tiles.len() as u64);
+        let mut last_id = 0;
+        for (tile_id, _) in &tiles {
+            write_varint(&mut directory, tile_id - last_id);
+            last_id = *tile_id;
+        }
+        tiles.iter().for_each(|_| write_varint(&mut directory, 1));
+        for (_, bytes) in &tiles {
+            write_varint(&mut directory, bytes.len() as u64);
+        }
+        let mut offset = 0;
+        for (_, bytes) in &tiles {
+            write_varint(&mut directory, offset + 1);
+            offset += bytes.len() as u64;
+        }
+        let metadata = br#"{"attribution":"(c) Test"}"#;
+
+        let root_offset = HEADER_LEN as u64;
+        let metadata_offset = root_offset + directory.len() as u64;
+        let data_offset = metadata_offset + metadata.len() as u64;
+
+        let mut header = vec![0; HEADER_LEN];
+        header[0..7].copy_from_slice(b"PMTiles");
+        header[7] = 3;
+        header[8..16].copy_from_slice(&root_offset.to_le_bytes());
+        header[16..24].copy_from_slice(&(directory.len() as u64).to_le_bytes());
+        header[24..32].copy_from_slice(&metadata_offset.to_le_bytes());
+        header[32..40].copy_from_slice(&(metadata.len() as u64).to_le_bytes());
+        header[56..64].copy_from_slice(&data_offset.to_le_bytes());
+        header[97] = 1;
+        header[98] = 1;
+        header[99] = 2;
+        header[100] = 0;
+        header[101] = 14;
+        header[118] = 6;
+        header[119..123].copy_from_slice(&249_000_000_i32.to_le_bytes());
+        header[123..127].copy_from_slice(&601_000_000_i32.to_le_bytes());
+
+        let path = std::env::temp_dir().join(format!(
+            "egui-map-view-test-{}-{name}.pmtiles",
+            std::process::id()
+        ));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(&header).unwrap();
+        file.write_all(&directory).unwrap();
+        file.write_all(metadata).unwrap();
+        for (_, bytes) in &tiles {
+            file.write_all(bytes).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn tile_ids_follow_hilbert_curve() {
+        assert_eq!(zxy_to_tile_id(0, 0, 0), 0);
+        assert_eq!(zxy_to_tile_id(1, 0, 0), 1);
+        assert_eq!(zxy_to_tile_id(1, 0, 1), 2);
+        assert_eq!(zxy_to_tile_id(1, 1, 1), 3);
+        assert_eq!(zxy_to_tile_id(1, 1, 0), 4);
+        assert_eq!(zxy_to_tile_id(2, 0, 0), 5);
+        assert_eq!(zxy_to_tile_id(3, 7, 0), 84);
+    }
+
+    #[test]
+    fn pmtiles_reads_tiles_and_metadata() {
+        let a = TileId { z: 1, x: 1, y: 0 };
+        let b = TileId { z: 2, x: 3, y: 1 };
+        let path = test_archive("read", &[(a, b"first"), (b, b"second")]);
+
+        let config = PmTilesConfig::open(&path).unwrap();
+        assert_eq!(config.attribution().map(String::as_str), Some("(c) Test"));
+        assert_eq!(MapConfig::max_zoom(&config), 14);
+        assert_eq!(config.default_zoom(), 6);
+        assert!((config.default_center().0 - 24.9).abs() < 1e-9);
+        assert!((config.default_center().1 - 60.1).abs() < 1e-9);
+
+        let source = config.tile_source().unwrap();
+        assert_eq!(
+            source.tile_bytes(&a).unwrap().as_deref(),
+            Some(&b"first"[..])
+        );
+        assert_eq!(
+            source.tile_bytes(&b).unwrap().as_deref(),
+            Some(&b"second"[..])
+        );
+        assert_eq!(
+            source.tile_bytes(&TileId { z: 1, x: 0, y: 0 }).unwrap(),
+            None
+        );
+        assert_eq!(
+            source.tile_bytes(&TileId { z: 1, x: 2, y: 0 }).unwrap(),
+            None
+        );
+
+        drop((config, source));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn pmtiles_rejects_corrupt_archives() {
+        let is_source_error = |result| matches!(result, Err(MapError::TileSourceError(_)));
+
+        // Tile ids that overflow.
+        let mut directory = Vec::new();
+        for value in [2, 1, u64::MAX, 1, 1, 1, 1, 1, 0] {
+            write_varint(&mut directory, value);
+        }
+        assert!(is_source_error(parse_directory(&directory)));
+
+        // An offset that overflows when the second tile follows the first one.
+        let mut directory = Vec::new();
+        for value in [2, 1, 1, 1, 1, 2, 1, u64::MAX, 0] {
+            write_varint(&mut directory, value);
+        }
+        assert!(is_source_error(parse_directory(&directory)));
+
+        // A tile that ends past the end of the archive.
+        let tile = TileId { z: 0, x: 0, y: 0 };
+        let path = test_archive("corrupt", &[(tile, b"tile")]);
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(file.metadata().unwrap().len() - 2).unwrap();
+        let archive = PmTiles::open(&path).unwrap();
+        assert!(matches!(
+            archive.tile_bytes(&tile),
+            Err(MapError::TileSourceError(_))
+        ));
+
+        // A root directory that ends past the end of the archive.
+        file.set_len(HEADER_LEN as u64 + 1).unwrap();
+        assert!(matches!(
+            PmTiles::open(&path),
+            Err(MapError::TileSourceError(_))
+        ));
+
+        drop((file, archive));
+        std::fs::remove_file(path).unwrap();
+    }
+}
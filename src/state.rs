@@ -0,0 +1,63 @@
+//! The state of the map view, for saving it and restoring it later.
+//!
+//! [`Map::state`](crate::Map::state) takes a [`MapState`] and
+//! [`Map::set_state`](crate::Map::set_state) restores it. A map with
+//! [`Map::with_persistence`](crate::Map::with_persistence) does this by itself through the
+//! persisted data of `egui::Memory`, which eframe saves between runs when its `persistence`
+//! feature is enabled.
+//!
+//! # Example
+//!
+//! Saving the state in the storage of eframe by hand:
+//!
+//! ```no_run
+//! use egui_map_view::{Map, state::MapState};
+//!
+//! fn save(storage: &mut dyn eframe::Storage, map: &Map) {
+//!     if let Ok(json) = serde_json::to_string(&map.state()) {
+//!         storage.set_string("map", json);
+//!     }
+//! }
+//!
+//! fn load(storage: &dyn eframe::Storage, map: &mut Map) {
+//!     if let Some(state) = storage
+//!         .get_string("map")
+//!         .and_then(|json| serde_json::from_str::<MapState>(&json).ok())
+//!     {
+//!         map.set_state(&state);
+//!     }
+//! }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::projection::GeoPos;
+
+/// The view of a map and the look of its layers.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MapState {
+    /// The geographical center of the map.
+    pub center: GeoPos,
+
+    /// The zoom level of the map.
+    pub zoom: f64,
+
+    /// The bearing of the map, in degrees clockwise from north.
+    #[serde(default)]
+    pub bearing: f64,
+
+    /// The state of the layers, by the key of the layer.
+    #[serde(default)]
+    pub layers: BTreeMap<String, LayerState>,
+}
+
+/// The look of a layer of the map.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LayerState {
+    /// The opacity of the layer, from 0.0 to 1.0.
+    pub opacity: f32,
+
+    /// Whether the layer is shown.
+    pub visible: bool,
+}